@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS blocks (
+    number BIGINT PRIMARY KEY,
+    hash TEXT NOT NULL,
+    leading_zeros BIGINT NOT NULL,
+    target_number BIGINT NOT NULL,
+    epoch_time BIGINT NOT NULL,
+    current_posix_time BIGINT NOT NULL,
+    nonce TEXT,
+    miner_cred TEXT,
+    nft_cred TEXT,
+    data TEXT,
+    cardano_tx_hash TEXT NOT NULL,
+    cardano_slot BIGINT NOT NULL,
+    cardano_hash TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS blocks_cardano_slot ON blocks (cardano_slot);
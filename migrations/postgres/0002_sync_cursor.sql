@@ -0,0 +1,6 @@
+-- The last Cardano block seine processed, updated for every block whether or
+-- not it contained a Fortuna block.
+CREATE TABLE IF NOT EXISTS sync_cursor (
+    slot BIGINT PRIMARY KEY,
+    hash TEXT NOT NULL
+);
@@ -0,0 +1,4 @@
+-- Lookups used by the REST API.
+CREATE INDEX IF NOT EXISTS blocks_cardano_tx_hash ON blocks (cardano_tx_hash);
+CREATE INDEX IF NOT EXISTS blocks_miner_cred ON blocks (miner_cred);
+CREATE INDEX IF NOT EXISTS blocks_nft_cred ON blocks (nft_cred);
@@ -0,0 +1,4 @@
+-- `target_number` and `epoch_time` can exceed 64 bits.
+ALTER TABLE blocks
+    ALTER COLUMN target_number TYPE NUMERIC,
+    ALTER COLUMN epoch_time TYPE NUMERIC;
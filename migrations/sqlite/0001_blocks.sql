@@ -0,0 +1,18 @@
+-- Used by both SQLite and Cloudflare D1.
+CREATE TABLE IF NOT EXISTS blocks (
+    number INTEGER PRIMARY KEY,
+    hash TEXT NOT NULL,
+    leading_zeros INTEGER NOT NULL,
+    target_number INTEGER NOT NULL,
+    epoch_time INTEGER NOT NULL,
+    current_posix_time INTEGER NOT NULL,
+    nonce TEXT,
+    miner_cred TEXT,
+    nft_cred TEXT,
+    data TEXT,
+    cardano_tx_hash TEXT NOT NULL,
+    cardano_slot INTEGER NOT NULL,
+    cardano_hash TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS blocks_cardano_slot ON blocks (cardano_slot);
@@ -0,0 +1,40 @@
+-- `target_number` and `epoch_time` can exceed 64 bits, so store them as
+-- decimal text. SQLite can't change a column's type in place, so the table is
+-- rebuilt.
+
+-- Migrations run in a transaction, so this only clears a table created outside
+-- of seine.
+DROP TABLE IF EXISTS blocks_new;
+
+CREATE TABLE IF NOT EXISTS blocks_new (
+    number INTEGER PRIMARY KEY,
+    hash TEXT NOT NULL,
+    leading_zeros INTEGER NOT NULL,
+    target_number TEXT NOT NULL,
+    epoch_time TEXT NOT NULL,
+    current_posix_time INTEGER NOT NULL,
+    nonce TEXT,
+    miner_cred TEXT,
+    nft_cred TEXT,
+    data TEXT,
+    cardano_tx_hash TEXT NOT NULL,
+    cardano_slot INTEGER NOT NULL,
+    cardano_hash TEXT NOT NULL
+);
+
+INSERT INTO blocks_new
+SELECT
+    number, hash, leading_zeros,
+    CAST(target_number AS TEXT), CAST(epoch_time AS TEXT),
+    current_posix_time, nonce, miner_cred, nft_cred, data,
+    cardano_tx_hash, cardano_slot, cardano_hash
+FROM blocks;
+
+DROP TABLE blocks;
+
+ALTER TABLE blocks_new RENAME TO blocks;
+
+CREATE INDEX IF NOT EXISTS blocks_cardano_slot ON blocks (cardano_slot);
+CREATE INDEX IF NOT EXISTS blocks_cardano_tx_hash ON blocks (cardano_tx_hash);
+CREATE INDEX IF NOT EXISTS blocks_miner_cred ON blocks (miner_cred);
+CREATE INDEX IF NOT EXISTS blocks_nft_cred ON blocks (nft_cred);
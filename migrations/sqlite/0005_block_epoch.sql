@@ -0,0 +1,11 @@
+-- Store each block's epoch so blocks can be filtered by it. Must match
+-- `consensus::epoch`, which a test in `database::sqlite` checks.
+ALTER TABLE blocks ADD COLUMN epoch INTEGER NOT NULL DEFAULT 0;
+
+UPDATE blocks
+SET epoch = CASE
+    WHEN number <= 30240 THEN number / 2016 + 1
+    ELSE (number - 30241) / 504 + 16
+END;
+
+CREATE INDEX blocks_epoch ON blocks (epoch);
@@ -0,0 +1,236 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{
+    database::{BlockQuery, StoredBlock},
+    server::AppState,
+};
+
+/// Page size when `limit` is not given.
+const DEFAULT_LIMIT: u64 = 20;
+
+/// The largest page a client can ask for.
+const MAX_LIMIT: u64 = 100;
+
+type ApiResult<T> = Result<Json<T>, (StatusCode, String)>;
+
+#[derive(Debug, Deserialize)]
+struct BlocksParams {
+    limit: Option<u64>,
+    offset: Option<u64>,
+    /// Payment or NFT credential of the miner, hex encoded.
+    miner: Option<String>,
+    epoch: Option<u64>,
+}
+
+/// Read-only routes over the indexed Fortuna blocks.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/blocks", get(blocks))
+        .route("/blocks/latest", get(latest))
+        .route("/blocks/:number", get(block))
+        .route("/tx/:cardano_tx_hash", get(block_by_tx))
+}
+
+/// Blocks newest first, optionally filtered by miner and epoch.
+async fn blocks(
+    State(state): State<AppState>,
+    Query(params): Query<BlocksParams>,
+) -> ApiResult<Vec<StoredBlock>> {
+    let query = BlockQuery {
+        miner: params.miner.map(|miner| miner.to_lowercase()),
+        epoch: params.epoch,
+        limit: params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+        offset: params.offset.unwrap_or(0),
+    };
+
+    state
+        .store
+        .blocks(&query)
+        .await
+        .map(Json)
+        .map_err(internal_error)
+}
+
+async fn latest(State(state): State<AppState>) -> ApiResult<StoredBlock> {
+    found(state.store.latest().await)
+}
+
+async fn block(State(state): State<AppState>, Path(number): Path<u64>) -> ApiResult<StoredBlock> {
+    found(state.store.block(number).await)
+}
+
+async fn block_by_tx(
+    State(state): State<AppState>,
+    Path(cardano_tx_hash): Path<String>,
+) -> ApiResult<StoredBlock> {
+    found(
+        state
+            .store
+            .block_by_tx(&cardano_tx_hash.to_lowercase())
+            .await,
+    )
+}
+
+fn found(result: miette::Result<Option<StoredBlock>>) -> ApiResult<StoredBlock> {
+    match result.map_err(internal_error)? {
+        Some(stored) => Ok(Json(stored)),
+        None => Err((StatusCode::NOT_FOUND, "block not found".to_string())),
+    }
+}
+
+fn internal_error(error: miette::Report) -> (StatusCode, String) {
+    error!(?error, "store query failed");
+
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "store query failed".to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use super::*;
+    use crate::{
+        database::{BlockStore, SqliteDatabase},
+        feed::Feed,
+        health::Health,
+    };
+
+    async fn state(name: &str, blocks: Vec<StoredBlock>) -> AppState {
+        let db = SqliteDatabase::temporary(name).await;
+
+        db.apply(1, "01", &blocks).await.unwrap();
+
+        AppState {
+            store: Arc::new(db),
+            health: Arc::new(Health::new(Duration::ZERO, Duration::ZERO)),
+            feed: Feed::new(std::num::NonZeroUsize::MIN),
+        }
+    }
+
+    fn params(limit: Option<u64>, offset: Option<u64>) -> BlocksParams {
+        BlocksParams {
+            limit,
+            offset,
+            miner: None,
+            epoch: None,
+        }
+    }
+
+    async fn numbers(state: &AppState, params: BlocksParams) -> Vec<u64> {
+        let Json(blocks) = blocks(State(state.clone()), Query(params)).await.unwrap();
+
+        blocks
+            .into_iter()
+            .map(|stored| stored.block.number)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn limit_defaults_and_is_clamped() {
+        let state = state(
+            "api-limit",
+            (1..=150)
+                .map(|number| StoredBlock::fixture(number, 1))
+                .collect(),
+        )
+        .await;
+
+        let default = numbers(&state, params(None, None)).await;
+
+        assert_eq!(default.len() as u64, DEFAULT_LIMIT);
+        assert_eq!(default.first(), Some(&150));
+
+        assert_eq!(
+            numbers(&state, params(Some(1_000), None)).await.len() as u64,
+            MAX_LIMIT
+        );
+        assert_eq!(
+            numbers(&state, params(Some(3), Some(10))).await,
+            vec![140, 139, 138]
+        );
+        assert!(numbers(&state, params(Some(0), None)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn filters_by_miner_in_any_case_and_epoch() {
+        let mut paid = StoredBlock::fixture(1, 1);
+        paid.block.payment_cred = Some("abcdef".to_string());
+
+        let mut nft = StoredBlock::fixture(2, 1);
+        nft.block.nft_cred = Some("abcdef".to_string());
+
+        let mut later = StoredBlock::fixture(3000, 1);
+        later.block.payment_cred = Some("abcdef".to_string());
+
+        let other = StoredBlock::fixture(3, 1);
+
+        let state = state("api-filter", vec![paid, nft, later, other]).await;
+
+        let by_miner = BlocksParams {
+            miner: Some("ABCdef".to_string()),
+            ..params(None, None)
+        };
+
+        assert_eq!(numbers(&state, by_miner).await, vec![3000, 2, 1]);
+
+        let by_miner_and_epoch = BlocksParams {
+            miner: Some("abcdef".to_string()),
+            epoch: Some(1),
+            ..params(None, None)
+        };
+
+        assert_eq!(numbers(&state, by_miner_and_epoch).await, vec![2, 1]);
+
+        let by_epoch = BlocksParams {
+            epoch: Some(2),
+            ..params(None, None)
+        };
+
+        assert_eq!(numbers(&state, by_epoch).await, vec![3000]);
+    }
+
+    #[tokio::test]
+    async fn single_blocks_are_found_or_404() {
+        let state = state("api-single", vec![StoredBlock::fixture(7, 1)]).await;
+
+        let Json(stored) = block(State(state.clone()), Path(7)).await.unwrap();
+        assert_eq!(stored.block.number, 7);
+
+        let Json(stored) = latest(State(state.clone())).await.unwrap();
+        assert_eq!(stored.block.number, 7);
+
+        // Looked up in lowercase, as stored.
+        let tx_hash = format!("{:064X}", 7);
+        let Json(stored) = block_by_tx(State(state.clone()), Path(tx_hash))
+            .await
+            .unwrap();
+        assert_eq!(stored.block.number, 7);
+
+        let missing = block(State(state.clone()), Path(8)).await.unwrap_err();
+        assert_eq!(missing.0, StatusCode::NOT_FOUND);
+
+        let missing = block_by_tx(State(state), Path("ff".to_string()))
+            .await
+            .unwrap_err();
+        assert_eq!(missing.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn latest_is_404_on_an_empty_store() {
+        let state = state("api-empty", vec![]).await;
+
+        let missing = latest(State(state)).await.unwrap_err();
+
+        assert_eq!(missing.0, StatusCode::NOT_FOUND);
+    }
+}
@@ -1,13 +1,20 @@
+use num_bigint::BigUint;
 use serde::{Deserialize, Serialize};
-use utxorpc::spec::cardano::{big_int::BigInt, plutus_data::PlutusData};
+use utxorpc::spec::cardano::plutus_data::PlutusData;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::plutus::Field;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TunaBlock {
     pub number: u64,
     pub current_hash: String,
     pub leading_zeros: u64,
-    pub target_number: u64,
-    pub epoch_time: u64,
+    /// Can exceed 64 bits on-chain, so it is serialized as a decimal string.
+    #[serde(with = "decimal")]
+    pub target_number: BigUint,
+    /// Can exceed 64 bits on-chain, so it is serialized as a decimal string.
+    #[serde(with = "decimal")]
+    pub epoch_time: BigUint,
     pub current_posix_time: u64,
     pub nonce: Option<String>,
     pub payment_cred: Option<String>,
@@ -23,110 +30,39 @@ impl TunaBlock {
     pub fn to_json_pretty(&self) -> serde_json::Result<String> {
         serde_json::to_string_pretty(self)
     }
+
+    /// Block `number` with everything else zeroed or empty, for tests to fill
+    /// in with struct update syntax.
+    #[cfg(test)]
+    pub fn fixture(number: u64) -> Self {
+        TunaBlock {
+            number,
+            current_hash: String::new(),
+            leading_zeros: 0,
+            target_number: 0u32.into(),
+            epoch_time: 0u32.into(),
+            current_posix_time: 0,
+            nonce: None,
+            payment_cred: None,
+            nft_cred: None,
+            data: None,
+        }
+    }
 }
 
 impl TryFrom<PlutusData> for TunaBlock {
     type Error = miette::Error;
 
     fn try_from(value: PlutusData) -> Result<Self, Self::Error> {
-        let PlutusData::Constr(constr) = value else {
-            miette::bail!("failed to decode tuna state");
-        };
-
-        let Some(number) = constr.fields[0]
-            .plutus_data
-            .as_ref()
-            .and_then(|data| match data {
-                PlutusData::BigInt(w) => w.big_int.as_ref(),
-                _ => None,
-            })
-            .and_then(|i| match i {
-                BigInt::Int(n) => Some(*n as u64),
-                _ => None,
-            })
-        else {
-            miette::bail!("failed to decode tuna state.number");
-        };
-
-        let Some(current_hash) =
-            constr.fields[1]
-                .plutus_data
-                .as_ref()
-                .and_then(|data| match data {
-                    PlutusData::BoundedBytes(b) => Some(hex::encode(b)),
-                    _ => None,
-                })
-        else {
-            miette::bail!("failed to decode tuna state.number");
-        };
-
-        let Some(leading_zeros) = constr.fields[2]
-            .plutus_data
-            .as_ref()
-            .and_then(|data| match data {
-                PlutusData::BigInt(w) => w.big_int.as_ref(),
-                _ => None,
-            })
-            .and_then(|i| match i {
-                BigInt::Int(n) => Some(*n as u64),
-                _ => None,
-            })
-        else {
-            miette::bail!("failed to decode tuna state.number");
-        };
-
-        let Some(target_number) = constr.fields[3]
-            .plutus_data
-            .as_ref()
-            .and_then(|data| match data {
-                PlutusData::BigInt(w) => w.big_int.as_ref(),
-                _ => None,
-            })
-            .and_then(|i| match i {
-                BigInt::Int(n) => Some(*n as u64),
-                _ => None,
-            })
-        else {
-            miette::bail!("failed to decode tuna state.number");
-        };
-
-        let Some(epoch_time) = constr.fields[4]
-            .plutus_data
-            .as_ref()
-            .and_then(|data| match data {
-                PlutusData::BigInt(w) => w.big_int.as_ref(),
-                _ => None,
-            })
-            .and_then(|i| match i {
-                BigInt::Int(n) => Some(*n as u64),
-                _ => None,
-            })
-        else {
-            miette::bail!("failed to decode tuna state.number");
-        };
-
-        let Some(current_posix_time) = constr.fields[5]
-            .plutus_data
-            .as_ref()
-            .and_then(|data| match data {
-                PlutusData::BigInt(w) => w.big_int.as_ref(),
-                _ => None,
-            })
-            .and_then(|i| match i {
-                BigInt::Int(n) => Some(*n as u64),
-                _ => None,
-            })
-        else {
-            miette::bail!("failed to decode tuna state.number");
-        };
+        let state = Field::root(&value, "state").constr()?;
 
         Ok(TunaBlock {
-            number,
-            current_hash,
-            leading_zeros,
-            target_number,
-            epoch_time,
-            current_posix_time,
+            number: state.field(0, "number")?.uint()?,
+            current_hash: state.field(1, "current_hash")?.hex()?,
+            leading_zeros: state.field(2, "leading_zeros")?.uint()?,
+            target_number: state.field(3, "target_number")?.biguint()?,
+            epoch_time: state.field(4, "epoch_time")?.biguint()?,
+            current_posix_time: state.field(5, "current_posix_time")?.uint()?,
             nonce: None,
             payment_cred: None,
             nft_cred: None,
@@ -134,3 +70,95 @@ impl TryFrom<PlutusData> for TunaBlock {
         })
     }
 }
+
+/// Serialize arbitrary-precision integers as decimal strings, which every
+/// JSON consumer can read without losing precision.
+mod decimal {
+    use std::fmt;
+
+    use num_bigint::BigUint;
+    use serde::{de, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &BigUint, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigUint, D::Error> {
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+
+    struct DecimalVisitor;
+
+    impl de::Visitor<'_> for DecimalVisitor {
+        type Value = BigUint;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a non-negative integer or decimal string")
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<BigUint, E> {
+            Ok(value.into())
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<BigUint, E> {
+            value.parse().map_err(E::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(target_number: BigUint, epoch_time: BigUint) -> TunaBlock {
+        TunaBlock {
+            target_number,
+            epoch_time,
+            ..TunaBlock::fixture(1)
+        }
+    }
+
+    #[test]
+    fn integers_beyond_64_bits_round_trip_as_decimal_strings() {
+        let big = BigUint::from(u64::MAX) * 1000u32 + 7u32;
+
+        let json = serde_json::to_value(block(big.clone(), big.clone())).unwrap();
+
+        assert_eq!(json["target_number"], "18446744073709551615007");
+        assert_eq!(json["epoch_time"], "18446744073709551615007");
+
+        let decoded: TunaBlock = serde_json::from_value(json).unwrap();
+
+        assert_eq!(decoded.target_number, big);
+        assert_eq!(decoded.epoch_time, big);
+    }
+
+    #[test]
+    fn plain_json_numbers_are_still_accepted() {
+        let mut json = serde_json::to_value(block(1u32.into(), 2u32.into())).unwrap();
+
+        json["target_number"] = serde_json::json!(u64::MAX);
+
+        let decoded: TunaBlock = serde_json::from_value(json).unwrap();
+
+        assert_eq!(decoded.target_number, BigUint::from(u64::MAX));
+    }
+
+    #[test]
+    fn negative_or_malformed_values_are_rejected() {
+        let mut json = serde_json::to_value(block(1u32.into(), 2u32.into())).unwrap();
+
+        for bad in [
+            serde_json::json!("-1"),
+            serde_json::json!("1e3"),
+            serde_json::json!(-1),
+        ] {
+            json["epoch_time"] = bad.clone();
+
+            assert!(
+                serde_json::from_value::<TunaBlock>(json.clone()).is_err(),
+                "{bad}"
+            );
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use std::{env, fmt::Display, str::FromStr};
+
+use miette::IntoDiagnostic;
+
+/// Read and parse an optional environment variable, falling back to `default`
+/// when it is unset.
+pub fn env_or<T>(key: &str, default: T) -> miette::Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .map_err(|error| miette::miette!("invalid {key}: {error}")),
+        Err(env::VarError::NotPresent) => Ok(default),
+        Err(error) => Err(error).into_diagnostic(),
+    }
+}
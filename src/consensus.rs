@@ -0,0 +1,150 @@
+//! Parameters of each Fortuna version, and what follows from them for any
+//! block number: its version, epoch, halving era and reward.
+
+use crate::{
+    constants::{TUNA_V1_ADDRESS, TUNA_V1_POLICY_ID, TUNA_V2_ADDRESS, TUNA_V2_POLICY_ID},
+    difficulty::Retarget,
+};
+
+/// The first block mined under V2.
+pub const HARD_FORK_BLOCK: u64 = 30241;
+
+/// Epochs completed under V1 before the hard fork.
+const V1_EPOCHS: u64 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Params {
+    pub version: Version,
+    /// Epoch length and target duration.
+    pub retarget: Retarget,
+    /// Blocks between reward halvings.
+    pub halving_interval: u64,
+    /// Reward of the first era, in the smallest TUNA unit.
+    pub initial_reward: u64,
+    /// Script address holding the state.
+    pub address: &'static [u8],
+    /// Policy of the state token and the minted TUNA.
+    pub policy_id: &'static [u8],
+}
+
+pub const V1: Params = Params {
+    version: Version::V1,
+    retarget: Retarget {
+        epoch_length: 2016,
+        epoch_target: 1_209_600_000,
+    },
+    halving_interval: 210_000,
+    initial_reward: 5_000_000_000,
+    address: TUNA_V1_ADDRESS,
+    policy_id: TUNA_V1_POLICY_ID,
+};
+
+pub const V2: Params = Params {
+    version: Version::V2,
+    retarget: Retarget {
+        epoch_length: 504,
+        epoch_target: 302_400_000,
+    },
+    halving_interval: 210_000,
+    initial_reward: 5_000_000_000,
+    address: TUNA_V2_ADDRESS,
+    policy_id: TUNA_V2_POLICY_ID,
+};
+
+/// The parameters block `number` was mined under.
+pub fn params(number: u64) -> &'static Params {
+    if number < HARD_FORK_BLOCK {
+        &V1
+    } else {
+        &V2
+    }
+}
+
+/// The epoch of block `number`, counting from 1.
+///
+/// V2 epochs continue V1's numbering; the last V1 block falls in the first
+/// V2 epoch.
+pub fn epoch(number: u64) -> u64 {
+    let v1_blocks = V1_EPOCHS * V1.retarget.epoch_length;
+
+    if number <= v1_blocks {
+        number / V1.retarget.epoch_length + 1
+    } else {
+        (number - HARD_FORK_BLOCK) / V2.retarget.epoch_length + V1_EPOCHS + 1
+    }
+}
+
+/// The halving era of block `number`, counting from 0.
+pub fn halving_era(number: u64) -> u64 {
+    number / params(number).halving_interval
+}
+
+/// The TUNA minted for block `number`, in the smallest unit.
+pub fn reward(number: u64) -> u64 {
+    let era = halving_era(number);
+
+    params(number)
+        .initial_reward
+        .checked_shr(era.try_into().unwrap_or(u32::MAX))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hard_fork_switches_parameters() {
+        assert_eq!(params(0).version, Version::V1);
+        assert_eq!(params(30240).version, Version::V1);
+        assert_eq!(params(30241).version, Version::V2);
+        assert_eq!(params(210000).version, Version::V2);
+    }
+
+    #[test]
+    fn v1_epochs_are_2016_blocks() {
+        assert_eq!(epoch(0), 1);
+        assert_eq!(epoch(2015), 1);
+        assert_eq!(epoch(2016), 2);
+        assert_eq!(epoch(2017), 2);
+        assert_eq!(epoch(30239), 15);
+    }
+
+    #[test]
+    fn last_v1_block_falls_in_the_first_v2_epoch() {
+        assert_eq!(epoch(30240), 16);
+        assert_eq!(epoch(30241), 16);
+    }
+
+    #[test]
+    fn v2_epochs_are_504_blocks() {
+        assert!(V2.retarget.retargets_after(30744));
+        assert_eq!(epoch(30744), 16);
+        assert_eq!(epoch(30745), 17);
+        assert_eq!(epoch(30745 + 504), 18);
+    }
+
+    #[test]
+    fn reward_halves_every_210000_blocks() {
+        assert_eq!(halving_era(209999), 0);
+        assert_eq!(halving_era(210000), 1);
+        assert_eq!(halving_era(210001), 1);
+
+        assert_eq!(reward(209999), 5_000_000_000);
+        assert_eq!(reward(210000), 2_500_000_000);
+        assert_eq!(reward(210001), 2_500_000_000);
+        assert_eq!(reward(420000), 1_250_000_000);
+    }
+
+    #[test]
+    fn reward_runs_out() {
+        assert_eq!(reward(210000 * 33), 0);
+        assert_eq!(reward(u64::MAX), 0);
+    }
+}
@@ -21,6 +21,17 @@ pub const TUNA_V2_POLICY_ID: &[u8] = &[
     0x7f, 0x68, 0x4b, 0xcb, 0x6f, 0x50, 0xa6, 0x36, 0x75, 0x3d, 0xa4, 0x8e,
 ];
 
+/// First slot of the Shelley era on mainnet; slots last one second from here on.
+pub const SHELLEY_START_SLOT: u64 = 4492800;
+
+/// POSIX time (in seconds) of [`SHELLEY_START_SLOT`].
+pub const SHELLEY_START_POSIX_TIME: u64 = 1596059091;
+
+/// POSIX time (in seconds) at which a post-Shelley mainnet slot began.
+pub fn slot_to_posix_time(slot: u64) -> u64 {
+    SHELLEY_START_POSIX_TIME + slot.saturating_sub(SHELLEY_START_SLOT)
+}
+
 pub fn initial_point() -> BlockRef {
     BlockRef {
         index: 101511708,
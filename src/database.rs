@@ -1,199 +1,278 @@
-use miette::IntoDiagnostic;
+use async_trait::async_trait;
+use miette::{IntoDiagnostic, WrapErr};
+use serde::{Deserialize, Serialize};
 use utxorpc::spec::sync::BlockRef;
 
-use crate::{block::TunaBlock, constants::initial_point};
+use crate::block::TunaBlock;
 
-#[derive(Debug, serde::Deserialize)]
-struct QueryResponse<T> {
-    results: Vec<T>,
-    success: bool,
-}
+mod d1;
+mod migrations;
+mod postgres;
+mod sql;
+mod sqlite;
 
-#[derive(Debug, serde::Deserialize)]
-struct TipPayload {
-    cardano_hash: String,
-    cardano_slot: u64,
+pub use d1::Database;
+pub use postgres::PostgresDatabase;
+pub use sqlite::SqliteDatabase;
+
+/// A Fortuna block together with the Cardano location it was indexed from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredBlock {
+    #[serde(flatten)]
+    pub block: TunaBlock,
+    pub cardano_tx_hash: String,
+    pub cardano_slot: u64,
+    pub cardano_hash: String,
+    /// Derived from the block number, and stored for filtering.
+    pub epoch: u64,
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct TipResponse {
-    result: Vec<QueryResponse<TipPayload>>,
-    success: bool,
+#[cfg(test)]
+impl StoredBlock {
+    /// [`TunaBlock::fixture`] minted in the Cardano block at `cardano_slot`,
+    /// with hashes derived from both.
+    pub fn fixture(number: u64, cardano_slot: u64) -> Self {
+        StoredBlock {
+            block: TunaBlock::fixture(number),
+            cardano_tx_hash: format!("{number:064x}"),
+            cardano_slot,
+            cardano_hash: format!("{cardano_slot:064x}"),
+            epoch: crate::consensus::epoch(number),
+        }
+    }
 }
 
-pub struct Database {
-    client: reqwest::Client,
-    endpoint: String,
-    d1_token: String,
+/// Filters and pagination for [`BlockStore::blocks`]. Results are ordered by
+/// block number, newest first.
+#[derive(Debug, Clone, Default)]
+pub struct BlockQuery {
+    /// Only blocks mined by this payment or NFT credential (hex).
+    pub miner: Option<String>,
+    /// Only blocks in this epoch.
+    pub epoch: Option<u64>,
+    pub limit: u64,
+    pub offset: u64,
 }
 
-impl Database {
-    pub fn new(account_id: String, database_id: String, d1_token: String) -> Self {
-        Self {
-            client: reqwest::Client::new(),
-            endpoint: format!(
-                "https://api.cloudflare.com/client/v4/accounts/{account_id}/d1/database/{database_id}/query"
-            ),
-            d1_token,
-        }
-    }
+/// Storage for indexed Fortuna blocks.
+///
+/// The sync loop in `main.rs` only talks to the store through this trait, so
+/// any backend (or test double) can be plugged in without touching it.
+#[async_trait]
+pub trait BlockStore: Send + Sync {
+    /// Bring the schema up to date, returning how many migrations were applied.
+    async fn migrate(&self) -> miette::Result<usize>;
 
-    pub async fn tip(&self) -> miette::Result<BlockRef> {
-        let res: TipResponse = self
-            .client
-            .post(&self.endpoint)
-            .bearer_auth(&self.d1_token)
-            .json(&serde_json::json!({
-                "sql": r#"
-                    SELECT cardano_slot, cardano_hash
-                    FROM blocks
-                    ORDER BY number DESC
-                    LIMIT 1
-                "#,
-            }))
-            .send()
-            .await
-            .into_diagnostic()?
-            .json()
-            .await
-            .into_diagnostic()?;
-
-        if res.success && !res.result.is_empty() && res.result[0].success {
-            let payload = &res.result[0].results[0];
-
-            Ok(BlockRef {
-                index: payload.cardano_slot,
-                hash: hex::decode(&payload.cardano_hash).into_diagnostic()?.into(),
-            })
-        } else {
-            Ok(initial_point())
+    /// The Cardano point to resume syncing from: the sync cursor, or the last
+    /// Fortuna block for databases synced before the cursor existed, or the
+    /// initial point for an empty database.
+    async fn tip(&self) -> miette::Result<BlockRef>;
+
+    /// The most recent Cardano points seine processed, newest first, at most
+    /// [`HISTORY_DEPTH`] of them.
+    async fn history(&self) -> miette::Result<Vec<BlockRef>>;
+
+    /// Points to intersect the chain with when (re)connecting, newest first.
+    ///
+    /// Offering a spread of recent points rather than just the tip means a
+    /// fork that happened while seine was down still leaves a common point.
+    async fn intersect(&self) -> miette::Result<Vec<BlockRef>> {
+        let points = spread(self.history().await?);
+
+        if points.is_empty() {
+            return Ok(vec![self.tip().await?]);
         }
+
+        Ok(points)
     }
 
-    pub async fn apply(
+    /// Insert or replace the Fortuna blocks found in one Cardano block, and
+    /// move the sync cursor to that block.
+    ///
+    /// The writes are atomic: either every block and the cursor are stored or
+    /// nothing is. Called for every Cardano block, even without Fortuna blocks.
+    /// Cursor entries beyond [`HISTORY_DEPTH`] are pruned.
+    async fn apply(
         &self,
-        block: &TunaBlock,
-        cardano_tx_hash: &str,
         cardano_slot: u64,
         cardano_hash: &str,
-    ) -> miette::Result<()> {
-        let value: serde_json::Value = self
-            .client
-            .post(&self.endpoint)
-            .bearer_auth(&self.d1_token)
-            .json(&serde_json::json!({
-                "sql": r#"
-                    INSERT INTO blocks (
-                        number, hash, leading_zeros,
-                        target_number, epoch_time,
-                        current_posix_time, nonce, miner_cred,
-                        nft_cred, data, cardano_tx_hash, cardano_slot,
-                        cardano_hash
-                      )
-                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                    ON CONFLICT (number) DO UPDATE SET
-                        hash = excluded.hash,
-                        leading_zeros = excluded.leading_zeros,
-                        target_number = excluded.target_number,
-                        epoch_time = excluded.epoch_time,
-                        current_posix_time = excluded.current_posix_time,
-                        nonce = excluded.nonce,
-                        miner_cred = excluded.miner_cred,
-                        nft_cred = excluded.nft_cred,
-                        data = excluded.data,
-                        cardano_tx_hash = excluded.cardano_tx_hash,
-                        cardano_slot = excluded.cardano_slot,
-                        cardano_hash = excluded.cardano_hash
-                "#,
-                "params": [
-                    block.number,
-                    block.current_hash,
-                    block.leading_zeros,
-                    block.target_number,
-                    block.epoch_time,
-                    block.current_posix_time,
-                    block.nonce,
-                    block.payment_cred,
-                    block.nft_cred,
-                    block.data,
-                    cardano_tx_hash,
-                    cardano_slot,
-                    cardano_hash,
-                ]
-            }))
-            .send()
-            .await
-            .into_diagnostic()?
-            .json()
-            .await
-            .into_diagnostic()?;
-
-        if value["success"].as_bool().unwrap() {
-            println!("applied tuna block {}", block.number);
-
-            Ok(())
-        } else {
-            println!("failed to insert {}", block.number);
-
-            miette::bail!("failed to insert block")
-        }
+        blocks: &[StoredBlock],
+    ) -> miette::Result<()>;
+
+    /// Remove every block and cursor entry at or after `slot`.
+    async fn undo(&self, slot: u64) -> miette::Result<()>;
+
+    /// Remove every block indexed after `point` and move the cursor to it.
+    async fn reset(&self, point: BlockRef) -> miette::Result<()>;
+
+    /// Look up a Fortuna block by its number.
+    async fn block(&self, number: u64) -> miette::Result<Option<StoredBlock>>;
+
+    /// The Fortuna block with the highest number.
+    async fn latest(&self) -> miette::Result<Option<StoredBlock>>;
+
+    /// The Fortuna block minted by a Cardano transaction.
+    async fn block_by_tx(&self, cardano_tx_hash: &str) -> miette::Result<Option<StoredBlock>>;
+
+    /// A page of Fortuna blocks matching `query`.
+    async fn blocks(&self, query: &BlockQuery) -> miette::Result<Vec<StoredBlock>>;
+}
+
+/// How many Cardano points the sync cursor keeps: the security parameter `k`,
+/// beyond which a block can no longer be rolled back.
+pub const HISTORY_DEPTH: u64 = 2160;
+
+/// How many of the newest points [`spread`] keeps before thinning out.
+const DENSE_POINTS: usize = 10;
+
+/// Pick intersection candidates from `history` (newest first): the newest
+/// [`DENSE_POINTS`] points, then exponentially sparser ones, then the oldest.
+fn spread(history: Vec<BlockRef>) -> Vec<BlockRef> {
+    let len = history.len();
+
+    if len == 0 {
+        return history;
     }
 
-    pub async fn undo(&self, slot: u64) -> miette::Result<()> {
-        let value: serde_json::Value = self
-            .client
-            .post(&self.endpoint)
-            .bearer_auth(&self.d1_token)
-            .json(&serde_json::json!({
-                "sql": r#"
-                    DELETE FROM blocks WHERE cardano_slot >= ?
-                "#,
-                "params": [
-                    slot,
-                ]
-            }))
-            .send()
-            .await
-            .into_diagnostic()?
-            .json()
-            .await
-            .into_diagnostic()?;
-
-        if value["success"].as_bool().unwrap() {
-            println!("undid {}", slot);
-
-            Ok(())
-        } else {
-            miette::bail!("failed to undo {}", slot)
+    let mut index = 0;
+    let mut step = 1;
+    let mut indices = Vec::new();
+
+    while index < len {
+        indices.push(index);
+
+        if indices.len() >= DENSE_POINTS {
+            step *= 2;
         }
+
+        index += step;
+    }
+
+    if indices.last() != Some(&(len - 1)) {
+        indices.push(len - 1);
     }
 
-    pub async fn reset(&self, point: BlockRef) -> miette::Result<()> {
-        let value: serde_json::Value = self
-            .client
-            .post(&self.endpoint)
-            .bearer_auth(&self.d1_token)
-            .json(&serde_json::json!({
-                "sql": r#"
-                    DELETE FROM blocks WHERE cardano_slot > ?
-                "#,
-                "params": [
-                    point.index,
-                ]
-            }))
-            .send()
-            .await
-            .into_diagnostic()?
-            .json()
-            .await
-            .into_diagnostic()?;
-
-        if value["success"].as_bool().unwrap() {
-            println!("reset to {}", point.index);
-
-            Ok(())
-        } else {
-            miette::bail!("failed to reset to {}", point.index)
+    history
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| indices.contains(index))
+        .map(|(_, point)| point)
+        .collect()
+}
+
+/// A row of the `blocks` table, using the column names of the schema.
+#[derive(Debug, Deserialize, sqlx::FromRow)]
+struct BlockRow {
+    number: i64,
+    hash: String,
+    leading_zeros: i64,
+    target_number: String,
+    epoch_time: String,
+    current_posix_time: i64,
+    nonce: Option<String>,
+    miner_cred: Option<String>,
+    nft_cred: Option<String>,
+    data: Option<String>,
+    cardano_tx_hash: String,
+    cardano_slot: i64,
+    cardano_hash: String,
+    epoch: i64,
+}
+
+impl TryFrom<BlockRow> for StoredBlock {
+    type Error = miette::Report;
+
+    fn try_from(row: BlockRow) -> miette::Result<Self> {
+        Ok(StoredBlock {
+            block: TunaBlock {
+                number: row.number as u64,
+                current_hash: row.hash,
+                leading_zeros: row.leading_zeros as u64,
+                target_number: row
+                    .target_number
+                    .parse()
+                    .into_diagnostic()
+                    .wrap_err("invalid blocks.target_number")?,
+                epoch_time: row
+                    .epoch_time
+                    .parse()
+                    .into_diagnostic()
+                    .wrap_err("invalid blocks.epoch_time")?,
+                current_posix_time: row.current_posix_time as u64,
+                nonce: row.nonce,
+                payment_cred: row.miner_cred,
+                nft_cred: row.nft_cred,
+                data: row.data,
+            },
+            cardano_tx_hash: row.cardano_tx_hash,
+            cardano_slot: row.cardano_slot as u64,
+            cardano_hash: row.cardano_hash,
+            epoch: row.epoch as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points whose slot is their position in the history.
+    fn history(len: u64) -> Vec<BlockRef> {
+        (0..len)
+            .map(|index| BlockRef {
+                index,
+                hash: Default::default(),
+            })
+            .collect()
+    }
+
+    fn spread_indices(len: u64) -> Vec<u64> {
+        spread(history(len))
+            .into_iter()
+            .map(|point| point.index)
+            .collect()
+    }
+
+    #[test]
+    fn spread_of_nothing_is_empty() {
+        assert!(spread_indices(0).is_empty());
+    }
+
+    #[test]
+    fn spread_of_one_point_keeps_it() {
+        assert_eq!(spread_indices(1), vec![0]);
+    }
+
+    #[test]
+    fn spread_keeps_short_histories_whole() {
+        assert_eq!(spread_indices(10), (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn spread_thins_out_exponentially() {
+        let indices = spread_indices(100);
+
+        assert_eq!(&indices[..DENSE_POINTS], &(0..10).collect::<Vec<_>>()[..]);
+        assert_eq!(&indices[DENSE_POINTS..], &[11, 15, 23, 39, 71, 99]);
+    }
+
+    #[test]
+    fn spread_of_full_history_ends_with_the_oldest_point() {
+        let indices = spread_indices(HISTORY_DEPTH);
+
+        assert_eq!(indices.first(), Some(&0));
+        assert_eq!(indices.last(), Some(&(HISTORY_DEPTH - 1)));
+
+        let gaps: Vec<u64> = indices[DENSE_POINTS - 1..]
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .collect();
+
+        // Doubling gaps, except the last one to the oldest point.
+        for pair in gaps[..gaps.len() - 1].windows(2) {
+            assert_eq!(pair[1], pair[0] * 2);
         }
+
+        assert!(indices.len() < 25);
     }
 }
@@ -0,0 +1,365 @@
+use async_trait::async_trait;
+use miette::{IntoDiagnostic, WrapErr};
+use serde::de::DeserializeOwned;
+use tracing::{error, info};
+use utxorpc::spec::sync::BlockRef;
+
+use super::{
+    migrations::{self, SCHEMA_VERSION_TABLE, SQLITE},
+    sql, BlockQuery, BlockRow, BlockStore, StoredBlock, HISTORY_DEPTH,
+};
+use crate::{constants::initial_point, metrics};
+
+#[derive(Debug, serde::Deserialize)]
+struct QueryResponse<T> {
+    results: Vec<T>,
+    success: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct PointPayload {
+    slot: u64,
+    hash: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct VersionPayload {
+    version: i64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct D1Response<T> {
+    result: Vec<QueryResponse<T>>,
+    success: bool,
+}
+
+pub struct Database {
+    client: reqwest::Client,
+    endpoint: String,
+    d1_token: String,
+}
+
+impl Database {
+    pub fn new(account_id: String, database_id: String, d1_token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: format!(
+                "https://api.cloudflare.com/client/v4/accounts/{account_id}/d1/database/{database_id}/query"
+            ),
+            d1_token,
+        }
+    }
+
+    /// Send one request to the query endpoint, failing unless D1 reports
+    /// every statement in it as successful.
+    async fn post<T: DeserializeOwned>(
+        &self,
+        body: serde_json::Value,
+    ) -> miette::Result<D1Response<T>> {
+        let timer = metrics::D1_REQUEST_SECONDS.start_timer();
+
+        let res: miette::Result<D1Response<T>> = async {
+            self.client
+                .post(&self.endpoint)
+                .bearer_auth(&self.d1_token)
+                .json(&body)
+                .send()
+                .await
+                .into_diagnostic()?
+                .json()
+                .await
+                .into_diagnostic()
+        }
+        .await;
+
+        timer.observe_duration();
+
+        match res {
+            Ok(res) if res.success && res.result.iter().all(|query| query.success) => Ok(res),
+            Ok(_) => {
+                metrics::D1_REQUEST_ERRORS.inc();
+
+                miette::bail!("d1 request was unsuccessful")
+            }
+            Err(error) => {
+                metrics::D1_REQUEST_ERRORS.inc();
+
+                Err(error)
+            }
+        }
+    }
+
+    async fn query<T: DeserializeOwned>(
+        &self,
+        sql: &str,
+        params: serde_json::Value,
+    ) -> miette::Result<Vec<T>> {
+        let res: D1Response<T> = self
+            .post(serde_json::json!({
+                "sql": sql,
+                "params": params,
+            }))
+            .await?;
+
+        match res.result.into_iter().next() {
+            Some(query) => Ok(query.results),
+            None => miette::bail!("d1 query returned no result"),
+        }
+    }
+
+    /// Run several parameterised statements in a single request. D1 executes
+    /// a batch as one transaction, so either all statements apply or none do.
+    async fn batch(&self, statements: Vec<serde_json::Value>) -> miette::Result<()> {
+        self.post::<serde_json::Value>(serde_json::json!({ "batch": statements }))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Run one or more parameterless statements in a single request.
+    async fn execute(&self, sql: &str) -> miette::Result<()> {
+        self.post::<serde_json::Value>(serde_json::json!({ "sql": sql }))
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BlockStore for Database {
+    async fn migrate(&self) -> miette::Result<usize> {
+        self.execute(SCHEMA_VERSION_TABLE).await?;
+
+        let rows: Vec<VersionPayload> = self
+            .query(
+                "SELECT COALESCE(MAX(version), 0) AS version FROM schema_version",
+                serde_json::json!([]),
+            )
+            .await?;
+
+        let current = rows.first().map_or(0, |row| row.version);
+
+        let pending = migrations::pending(SQLITE, current);
+
+        for migration in pending {
+            // One batch, so a migration that fails partway leaves nothing
+            // behind and is retried from the start.
+            let mut statements: Vec<serde_json::Value> = migrations::statements(migration.sql)
+                .into_iter()
+                .map(|sql| serde_json::json!({ "sql": sql, "params": [] }))
+                .collect();
+
+            statements.push(serde_json::json!({
+                "sql": "INSERT INTO schema_version (version, name) VALUES (?, ?)",
+                "params": [migration.version, migration.name],
+            }));
+
+            self.batch(statements)
+                .await
+                .wrap_err_with(|| format!("migration {} failed", migration.version))?;
+
+            info!(
+                version = migration.version,
+                name = migration.name,
+                "applied migration"
+            );
+        }
+
+        Ok(pending.len())
+    }
+
+    async fn tip(&self) -> miette::Result<BlockRef> {
+        let cursor: Vec<PointPayload> = self.query(sql::CURSOR_TIP, serde_json::json!([])).await?;
+
+        let payload = match cursor.into_iter().next() {
+            Some(payload) => Some(payload),
+            None => self
+                .query::<PointPayload>(sql::LAST_BLOCK_POINT, serde_json::json!([]))
+                .await?
+                .into_iter()
+                .next(),
+        };
+
+        match payload {
+            Some(payload) => Ok(BlockRef {
+                index: payload.slot,
+                hash: hex::decode(&payload.hash).into_diagnostic()?.into(),
+            }),
+            None => Ok(initial_point()),
+        }
+    }
+
+    async fn history(&self) -> miette::Result<Vec<BlockRef>> {
+        let rows: Vec<PointPayload> = self.query(sql::HISTORY, serde_json::json!([])).await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(BlockRef {
+                    index: row.slot,
+                    hash: hex::decode(&row.hash).into_diagnostic()?.into(),
+                })
+            })
+            .collect()
+    }
+
+    async fn apply(
+        &self,
+        cardano_slot: u64,
+        cardano_hash: &str,
+        blocks: &[StoredBlock],
+    ) -> miette::Result<()> {
+        let mut statements: Vec<serde_json::Value> = blocks
+            .iter()
+            .map(|stored| {
+                serde_json::json!({
+                    "sql": sql::upsert_block("TEXT"),
+                    "params": [
+                        stored.block.number,
+                        stored.block.current_hash,
+                        stored.block.leading_zeros,
+                        stored.block.target_number.to_string(),
+                        stored.block.epoch_time.to_string(),
+                        stored.block.current_posix_time,
+                        stored.block.nonce,
+                        stored.block.payment_cred,
+                        stored.block.nft_cred,
+                        stored.block.data,
+                        stored.cardano_tx_hash,
+                        stored.cardano_slot,
+                        stored.cardano_hash,
+                        stored.epoch,
+                    ]
+                })
+            })
+            .collect();
+
+        statements.push(serde_json::json!({
+            "sql": sql::MOVE_CURSOR,
+            "params": [cardano_slot, cardano_hash],
+        }));
+
+        statements.push(serde_json::json!({
+            "sql": sql::PRUNE_HISTORY,
+            "params": [HISTORY_DEPTH - 1],
+        }));
+
+        if let Err(error) = self.batch(statements).await {
+            for stored in blocks {
+                error!(number = stored.block.number, "failed to insert");
+            }
+
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    async fn undo(&self, slot: u64) -> miette::Result<()> {
+        let statements = vec![
+            serde_json::json!({
+                "sql": sql::DELETE_BLOCKS_FROM,
+                "params": [slot],
+            }),
+            serde_json::json!({
+                "sql": sql::DELETE_CURSOR_FROM,
+                "params": [slot],
+            }),
+        ];
+
+        self.batch(statements)
+            .await
+            .wrap_err_with(|| format!("failed to undo {slot}"))?;
+
+        Ok(())
+    }
+
+    async fn reset(&self, point: BlockRef) -> miette::Result<()> {
+        let statements = vec![
+            serde_json::json!({
+                "sql": sql::DELETE_BLOCKS_AFTER,
+                "params": [point.index],
+            }),
+            serde_json::json!({
+                "sql": sql::DELETE_CURSOR_FROM,
+                "params": [point.index],
+            }),
+            serde_json::json!({
+                "sql": sql::MOVE_CURSOR,
+                "params": [point.index, hex::encode(&point.hash)],
+            }),
+        ];
+
+        self.batch(statements)
+            .await
+            .wrap_err_with(|| format!("failed to reset to {}", point.index))?;
+
+        Ok(())
+    }
+
+    async fn block(&self, number: u64) -> miette::Result<Option<StoredBlock>> {
+        let rows: Vec<BlockRow> = self
+            .query(
+                &sql::select_blocks("WHERE number = ?"),
+                serde_json::json!([number]),
+            )
+            .await?;
+
+        rows.into_iter()
+            .next()
+            .map(StoredBlock::try_from)
+            .transpose()
+    }
+
+    async fn latest(&self) -> miette::Result<Option<StoredBlock>> {
+        let rows: Vec<BlockRow> = self
+            .query(
+                &sql::select_blocks("ORDER BY number DESC LIMIT 1"),
+                serde_json::json!([]),
+            )
+            .await?;
+
+        rows.into_iter()
+            .next()
+            .map(StoredBlock::try_from)
+            .transpose()
+    }
+
+    async fn block_by_tx(&self, cardano_tx_hash: &str) -> miette::Result<Option<StoredBlock>> {
+        let rows: Vec<BlockRow> = self
+            .query(
+                &sql::select_blocks("WHERE cardano_tx_hash = ?"),
+                serde_json::json!([cardano_tx_hash]),
+            )
+            .await?;
+
+        rows.into_iter()
+            .next()
+            .map(StoredBlock::try_from)
+            .transpose()
+    }
+
+    async fn blocks(&self, query: &BlockQuery) -> miette::Result<Vec<StoredBlock>> {
+        // The same query as `sql::query_blocks`, with JSON parameters.
+        let mut sql = sql::select_blocks("WHERE 1 = 1");
+        let mut params = Vec::new();
+
+        if let Some(miner) = &query.miner {
+            sql.push_str(" AND (miner_cred = ? OR nft_cred = ?)");
+            params.push(serde_json::json!(miner));
+            params.push(serde_json::json!(miner));
+        }
+
+        if let Some(epoch) = query.epoch {
+            sql.push_str(" AND epoch = ?");
+            params.push(serde_json::json!(epoch));
+        }
+
+        sql.push_str(" ORDER BY number DESC LIMIT ? OFFSET ?");
+        params.push(serde_json::json!(query.limit));
+        params.push(serde_json::json!(query.offset));
+
+        let rows: Vec<BlockRow> = self.query(&sql, params.into()).await?;
+
+        rows.into_iter().map(StoredBlock::try_from).collect()
+    }
+}
@@ -0,0 +1,136 @@
+/// A versioned schema change, embedded in the binary.
+///
+/// Applied versions are recorded in the `schema_version` table, so each
+/// migration runs exactly once per database, in order.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// Migrations for SQLite, which Cloudflare D1 shares.
+pub const SQLITE: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "blocks",
+        sql: include_str!("../../migrations/sqlite/0001_blocks.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "sync_cursor",
+        sql: include_str!("../../migrations/sqlite/0002_sync_cursor.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "block_lookups",
+        sql: include_str!("../../migrations/sqlite/0003_block_lookups.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "lossless_integers",
+        sql: include_str!("../../migrations/sqlite/0004_lossless_integers.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "block_epoch",
+        sql: include_str!("../../migrations/sqlite/0005_block_epoch.sql"),
+    },
+];
+
+pub const POSTGRES: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "blocks",
+        sql: include_str!("../../migrations/postgres/0001_blocks.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "sync_cursor",
+        sql: include_str!("../../migrations/postgres/0002_sync_cursor.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "block_lookups",
+        sql: include_str!("../../migrations/postgres/0003_block_lookups.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "lossless_integers",
+        sql: include_str!("../../migrations/postgres/0004_lossless_integers.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "block_epoch",
+        sql: include_str!("../../migrations/postgres/0005_block_epoch.sql"),
+    },
+];
+
+pub const SCHEMA_VERSION_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS schema_version (
+        version BIGINT PRIMARY KEY,
+        name TEXT NOT NULL,
+        applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+    )
+"#;
+
+/// The migrations in `migrations` newer than `current`.
+pub fn pending(migrations: &'static [Migration], current: i64) -> &'static [Migration] {
+    let start = migrations
+        .iter()
+        .position(|migration| migration.version > current)
+        .unwrap_or(migrations.len());
+
+    &migrations[start..]
+}
+
+/// The statements in a migration, for backends that take one statement at a
+/// time. Drops `--` comment lines, so semicolons may appear in comments but
+/// not in string literals.
+pub fn statements(sql: &str) -> Vec<String> {
+    let code: Vec<&str> = sql
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("--"))
+        .collect();
+
+    code.join("\n")
+        .split(';')
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statements_skip_comments_and_blank_lines() {
+        let sql = "-- a comment; with a semicolon\nCREATE TABLE a (x INTEGER);\n\nDROP TABLE b;\n";
+
+        assert_eq!(
+            statements(sql),
+            vec!["CREATE TABLE a (x INTEGER)", "DROP TABLE b"]
+        );
+    }
+
+    #[test]
+    fn every_sqlite_migration_splits() {
+        for migration in SQLITE {
+            assert!(
+                !statements(migration.sql).is_empty(),
+                "{} has no statements",
+                migration.name
+            );
+        }
+
+        assert_eq!(statements(SQLITE[3].sql).len(), 9);
+    }
+
+    #[test]
+    fn pending_skips_applied_versions() {
+        assert_eq!(pending(SQLITE, 0).len(), SQLITE.len());
+        assert_eq!(pending(SQLITE, 3)[0].version, 4);
+        assert!(pending(SQLITE, SQLITE.len() as i64).is_empty());
+    }
+}
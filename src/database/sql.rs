@@ -0,0 +1,180 @@
+//! SQL shared by every backend, so their queries can't drift apart.
+//!
+//! Statements use SQLite's `?` placeholders, which D1 shares. Postgres numbers
+//! its placeholders, so its backend passes each statement through
+//! [`numbered`] first.
+
+use sqlx::{Database, Encode, QueryBuilder, Type};
+
+use super::BlockQuery;
+
+/// Columns of the `blocks` table, in the order [`BlockRow`] expects them.
+///
+/// The arbitrary-precision columns are read back as decimal text, which both
+/// SQLite and Postgres can cast to.
+///
+/// [`BlockRow`]: super::BlockRow
+pub const BLOCK_COLUMNS: &str = "number, hash, leading_zeros, \
+    CAST(target_number AS TEXT) AS target_number, CAST(epoch_time AS TEXT) AS epoch_time, \
+    current_posix_time, nonce, miner_cred, nft_cred, data, cardano_tx_hash, cardano_slot, \
+    cardano_hash, epoch";
+
+pub const CURSOR_TIP: &str = "SELECT slot, hash FROM sync_cursor ORDER BY slot DESC LIMIT 1";
+
+/// The point of the last Fortuna block, for databases synced before the
+/// cursor existed.
+pub const LAST_BLOCK_POINT: &str = r#"
+    SELECT cardano_slot AS slot, cardano_hash AS hash
+    FROM blocks
+    ORDER BY number DESC
+    LIMIT 1
+"#;
+
+pub const HISTORY: &str = "SELECT slot, hash FROM sync_cursor ORDER BY slot DESC";
+
+/// Move the cursor to a Cardano point: slot, hash.
+pub const MOVE_CURSOR: &str = r#"
+    INSERT INTO sync_cursor (slot, hash) VALUES (?, ?)
+    ON CONFLICT (slot) DO UPDATE SET hash = excluded.hash
+"#;
+
+/// Keep only the newest points: takes the depth minus one.
+pub const PRUNE_HISTORY: &str = r#"
+    DELETE FROM sync_cursor
+    WHERE slot < (SELECT slot FROM sync_cursor ORDER BY slot DESC LIMIT 1 OFFSET ?)
+"#;
+
+pub const DELETE_BLOCKS_FROM: &str = "DELETE FROM blocks WHERE cardano_slot >= ?";
+
+pub const DELETE_BLOCKS_AFTER: &str = "DELETE FROM blocks WHERE cardano_slot > ?";
+
+pub const DELETE_CURSOR_FROM: &str = "DELETE FROM sync_cursor WHERE slot >= ?";
+
+/// Insert or replace a block, binding the columns in schema order.
+///
+/// `target_number` and `epoch_time` are bound as decimal text and cast to
+/// `decimal`, the type the backend stores them as.
+pub fn upsert_block(decimal: &str) -> String {
+    format!(
+        r#"
+            INSERT INTO blocks (
+                number, hash, leading_zeros,
+                target_number, epoch_time,
+                current_posix_time, nonce, miner_cred,
+                nft_cred, data, cardano_tx_hash, cardano_slot,
+                cardano_hash, epoch
+              )
+            VALUES (?, ?, ?, CAST(? AS {decimal}), CAST(? AS {decimal}), ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (number) DO UPDATE SET
+                hash = excluded.hash,
+                leading_zeros = excluded.leading_zeros,
+                target_number = excluded.target_number,
+                epoch_time = excluded.epoch_time,
+                current_posix_time = excluded.current_posix_time,
+                nonce = excluded.nonce,
+                miner_cred = excluded.miner_cred,
+                nft_cred = excluded.nft_cred,
+                data = excluded.data,
+                cardano_tx_hash = excluded.cardano_tx_hash,
+                cardano_slot = excluded.cardano_slot,
+                cardano_hash = excluded.cardano_hash,
+                epoch = excluded.epoch
+        "#
+    )
+}
+
+/// Select blocks, followed by `rest` (a `WHERE` or `ORDER BY` clause).
+pub fn select_blocks(rest: &str) -> String {
+    format!("SELECT {BLOCK_COLUMNS} FROM blocks {rest}")
+}
+
+/// The page of blocks matching `query`. [`QueryBuilder`] writes each
+/// backend's own placeholders.
+pub fn query_blocks<'a, DB>(query: &BlockQuery) -> QueryBuilder<'a, DB>
+where
+    DB: Database,
+    String: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+{
+    let mut builder = QueryBuilder::new(select_blocks("WHERE 1 = 1"));
+
+    if let Some(miner) = &query.miner {
+        builder
+            .push(" AND (miner_cred = ")
+            .push_bind(miner.clone())
+            .push(" OR nft_cred = ")
+            .push_bind(miner.clone())
+            .push(")");
+    }
+
+    if let Some(epoch) = query.epoch {
+        builder.push(" AND epoch = ").push_bind(epoch as i64);
+    }
+
+    builder
+        .push(" ORDER BY number DESC LIMIT ")
+        .push_bind(query.limit as i64)
+        .push(" OFFSET ")
+        .push_bind(query.offset as i64);
+
+    builder
+}
+
+/// Rewrite `?` placeholders as Postgres' `$1`, `$2`, ... The statements
+/// above have no string literals, so every `?` is a placeholder.
+pub fn numbered(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len() + 16);
+    let mut count = 0;
+
+    for c in sql.chars() {
+        if c == '?' {
+            count += 1;
+            out.push_str(&format!("${count}"));
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Postgres, Sqlite};
+
+    use super::*;
+
+    #[test]
+    fn numbered_counts_placeholders() {
+        assert_eq!(
+            numbered("INSERT INTO t (a, b) VALUES (?, ?)"),
+            "INSERT INTO t (a, b) VALUES ($1, $2)"
+        );
+        assert_eq!(numbered(CURSOR_TIP), CURSOR_TIP);
+    }
+
+    #[test]
+    fn upsert_binds_every_column() {
+        assert_eq!(upsert_block("TEXT").matches('?').count(), 14);
+        assert!(numbered(&upsert_block("NUMERIC")).contains("CAST($4 AS NUMERIC)"));
+    }
+
+    #[test]
+    fn block_queries_use_each_backends_placeholders() {
+        let query = BlockQuery {
+            miner: Some("ab".to_string()),
+            epoch: Some(3),
+            limit: 10,
+            offset: 0,
+        };
+
+        let sqlite = query_blocks::<Sqlite>(&query).into_sql();
+        let postgres = query_blocks::<Postgres>(&query).into_sql();
+
+        assert!(sqlite.ends_with(
+            "WHERE 1 = 1 AND (miner_cred = ? OR nft_cred = ?) AND epoch = ? \
+             ORDER BY number DESC LIMIT ? OFFSET ?"
+        ));
+        assert_eq!(postgres, numbered(&sqlite));
+    }
+}
@@ -0,0 +1,506 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use miette::IntoDiagnostic;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::{Executor, Sqlite};
+use tracing::info;
+use utxorpc::spec::sync::BlockRef;
+
+use super::{
+    migrations::{self, SCHEMA_VERSION_TABLE, SQLITE},
+    sql, BlockQuery, BlockRow, BlockStore, StoredBlock, HISTORY_DEPTH,
+};
+use crate::constants::initial_point;
+
+/// A file-backed store, for local development and self-hosted indexing.
+pub struct SqliteDatabase {
+    pool: SqlitePool,
+}
+
+impl SqliteDatabase {
+    /// Open (or create) the database at `url`, e.g. `sqlite://seine.db`.
+    pub async fn connect(url: &str) -> miette::Result<Self> {
+        let options = SqliteConnectOptions::from_str(url)
+            .into_diagnostic()?
+            .create_if_missing(true);
+
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .into_diagnostic()?;
+
+        Ok(Self { pool })
+    }
+
+    /// A migrated database in a fresh temporary file, for tests.
+    #[cfg(test)]
+    pub async fn temporary(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("seine-{name}-{}.db", std::process::id()));
+
+        let _ = std::fs::remove_file(&path);
+
+        let db = Self::connect(&format!("sqlite://{}", path.display()))
+            .await
+            .unwrap();
+
+        db.migrate().await.unwrap();
+
+        db
+    }
+}
+
+#[async_trait]
+impl BlockStore for SqliteDatabase {
+    async fn migrate(&self) -> miette::Result<usize> {
+        self.pool
+            .execute(sqlx::raw_sql(SCHEMA_VERSION_TABLE))
+            .await
+            .into_diagnostic()?;
+
+        let (current,): (i64,) =
+            sqlx::query_as("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+                .fetch_one(&self.pool)
+                .await
+                .into_diagnostic()?;
+
+        let pending = migrations::pending(SQLITE, current);
+
+        for migration in pending {
+            let mut tx = self.pool.begin().await.into_diagnostic()?;
+
+            tx.execute(sqlx::raw_sql(migration.sql))
+                .await
+                .into_diagnostic()?;
+
+            sqlx::query("INSERT INTO schema_version (version, name) VALUES (?, ?)")
+                .bind(migration.version)
+                .bind(migration.name)
+                .execute(&mut *tx)
+                .await
+                .into_diagnostic()?;
+
+            tx.commit().await.into_diagnostic()?;
+
+            info!(
+                version = migration.version,
+                name = migration.name,
+                "applied migration"
+            );
+        }
+
+        Ok(pending.len())
+    }
+
+    async fn tip(&self) -> miette::Result<BlockRef> {
+        let cursor: Option<(i64, String)> = sqlx::query_as(sql::CURSOR_TIP)
+            .fetch_optional(&self.pool)
+            .await
+            .into_diagnostic()?;
+
+        let row = match cursor {
+            Some(row) => Some(row),
+            None => sqlx::query_as(sql::LAST_BLOCK_POINT)
+                .fetch_optional(&self.pool)
+                .await
+                .into_diagnostic()?,
+        };
+
+        match row {
+            Some((slot, hash)) => Ok(BlockRef {
+                index: slot as u64,
+                hash: hex::decode(hash).into_diagnostic()?.into(),
+            }),
+            None => Ok(initial_point()),
+        }
+    }
+
+    async fn history(&self) -> miette::Result<Vec<BlockRef>> {
+        let rows: Vec<(i64, String)> = sqlx::query_as(sql::HISTORY)
+            .fetch_all(&self.pool)
+            .await
+            .into_diagnostic()?;
+
+        rows.into_iter()
+            .map(|(slot, hash)| {
+                Ok(BlockRef {
+                    index: slot as u64,
+                    hash: hex::decode(hash).into_diagnostic()?.into(),
+                })
+            })
+            .collect()
+    }
+
+    async fn apply(
+        &self,
+        cardano_slot: u64,
+        cardano_hash: &str,
+        blocks: &[StoredBlock],
+    ) -> miette::Result<()> {
+        let mut tx = self.pool.begin().await.into_diagnostic()?;
+
+        for stored in blocks {
+            sqlx::query(&sql::upsert_block("TEXT"))
+                .bind(stored.block.number as i64)
+                .bind(&stored.block.current_hash)
+                .bind(stored.block.leading_zeros as i64)
+                .bind(stored.block.target_number.to_string())
+                .bind(stored.block.epoch_time.to_string())
+                .bind(stored.block.current_posix_time as i64)
+                .bind(&stored.block.nonce)
+                .bind(&stored.block.payment_cred)
+                .bind(&stored.block.nft_cred)
+                .bind(&stored.block.data)
+                .bind(&stored.cardano_tx_hash)
+                .bind(stored.cardano_slot as i64)
+                .bind(&stored.cardano_hash)
+                .bind(stored.epoch as i64)
+                .execute(&mut *tx)
+                .await
+                .into_diagnostic()?;
+        }
+
+        sqlx::query(sql::MOVE_CURSOR)
+            .bind(cardano_slot as i64)
+            .bind(cardano_hash)
+            .execute(&mut *tx)
+            .await
+            .into_diagnostic()?;
+
+        sqlx::query(sql::PRUNE_HISTORY)
+            .bind((HISTORY_DEPTH - 1) as i64)
+            .execute(&mut *tx)
+            .await
+            .into_diagnostic()?;
+
+        tx.commit().await.into_diagnostic()?;
+
+        Ok(())
+    }
+
+    async fn undo(&self, slot: u64) -> miette::Result<()> {
+        let mut tx = self.pool.begin().await.into_diagnostic()?;
+
+        sqlx::query(sql::DELETE_BLOCKS_FROM)
+            .bind(slot as i64)
+            .execute(&mut *tx)
+            .await
+            .into_diagnostic()?;
+
+        sqlx::query(sql::DELETE_CURSOR_FROM)
+            .bind(slot as i64)
+            .execute(&mut *tx)
+            .await
+            .into_diagnostic()?;
+
+        tx.commit().await.into_diagnostic()?;
+
+        Ok(())
+    }
+
+    async fn reset(&self, point: BlockRef) -> miette::Result<()> {
+        let mut tx = self.pool.begin().await.into_diagnostic()?;
+
+        sqlx::query(sql::DELETE_BLOCKS_AFTER)
+            .bind(point.index as i64)
+            .execute(&mut *tx)
+            .await
+            .into_diagnostic()?;
+
+        sqlx::query(sql::DELETE_CURSOR_FROM)
+            .bind(point.index as i64)
+            .execute(&mut *tx)
+            .await
+            .into_diagnostic()?;
+
+        sqlx::query(sql::MOVE_CURSOR)
+            .bind(point.index as i64)
+            .bind(hex::encode(&point.hash))
+            .execute(&mut *tx)
+            .await
+            .into_diagnostic()?;
+
+        tx.commit().await.into_diagnostic()?;
+
+        Ok(())
+    }
+
+    async fn block(&self, number: u64) -> miette::Result<Option<StoredBlock>> {
+        let row: Option<BlockRow> = sqlx::query_as(&sql::select_blocks("WHERE number = ?"))
+            .bind(number as i64)
+            .fetch_optional(&self.pool)
+            .await
+            .into_diagnostic()?;
+
+        row.map(StoredBlock::try_from).transpose()
+    }
+
+    async fn latest(&self) -> miette::Result<Option<StoredBlock>> {
+        let row: Option<BlockRow> =
+            sqlx::query_as(&sql::select_blocks("ORDER BY number DESC LIMIT 1"))
+                .fetch_optional(&self.pool)
+                .await
+                .into_diagnostic()?;
+
+        row.map(StoredBlock::try_from).transpose()
+    }
+
+    async fn block_by_tx(&self, cardano_tx_hash: &str) -> miette::Result<Option<StoredBlock>> {
+        let row: Option<BlockRow> =
+            sqlx::query_as(&sql::select_blocks("WHERE cardano_tx_hash = ?"))
+                .bind(cardano_tx_hash)
+                .fetch_optional(&self.pool)
+                .await
+                .into_diagnostic()?;
+
+        row.map(StoredBlock::try_from).transpose()
+    }
+
+    async fn blocks(&self, query: &BlockQuery) -> miette::Result<Vec<StoredBlock>> {
+        let rows: Vec<BlockRow> = sql::query_blocks::<Sqlite>(query)
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .into_diagnostic()?;
+
+        rows.into_iter().map(StoredBlock::try_from).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigUint;
+
+    use super::*;
+    use crate::{consensus, database::migrations::POSTGRES};
+
+    fn point(slot: u64) -> BlockRef {
+        BlockRef {
+            index: slot,
+            hash: hex::decode(format!("{slot:064x}")).unwrap().into(),
+        }
+    }
+
+    /// Apply the Cardano block at `slot`, minting the Fortuna blocks `numbers`.
+    async fn apply(db: &SqliteDatabase, slot: u64, numbers: &[u64]) {
+        let blocks: Vec<StoredBlock> = numbers
+            .iter()
+            .map(|&number| StoredBlock::fixture(number, slot))
+            .collect();
+
+        db.apply(slot, &hex::encode(point(slot).hash), &blocks)
+            .await
+            .unwrap();
+    }
+
+    async fn history_slots(db: &SqliteDatabase) -> Vec<u64> {
+        db.history()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|point| point.index)
+            .collect()
+    }
+
+    async fn block_numbers(db: &SqliteDatabase) -> Vec<u64> {
+        db.blocks(&BlockQuery {
+            limit: 100,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|stored| stored.block.number)
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn empty_database_starts_at_the_initial_point() {
+        let db = SqliteDatabase::temporary("empty-tip").await;
+
+        assert_eq!(db.tip().await.unwrap(), initial_point());
+        assert!(history_slots(&db).await.is_empty());
+        assert_eq!(db.intersect().await.unwrap(), vec![initial_point()]);
+    }
+
+    #[tokio::test]
+    async fn tip_follows_the_cursor_past_blocks_without_fortuna_blocks() {
+        let db = SqliteDatabase::temporary("cursor-tip").await;
+
+        apply(&db, 10, &[1]).await;
+        apply(&db, 20, &[]).await;
+
+        assert_eq!(db.tip().await.unwrap(), point(20));
+        assert_eq!(history_slots(&db).await, vec![20, 10]);
+    }
+
+    #[tokio::test]
+    async fn tip_falls_back_to_the_highest_block_without_a_cursor() {
+        let db = SqliteDatabase::temporary("fallback-tip").await;
+
+        apply(&db, 10, &[1]).await;
+        apply(&db, 30, &[2]).await;
+        apply(&db, 40, &[]).await;
+
+        db.pool.execute("DELETE FROM sync_cursor").await.unwrap();
+
+        assert_eq!(db.tip().await.unwrap(), point(30));
+    }
+
+    #[tokio::test]
+    async fn undo_removes_blocks_and_cursor_entries_from_the_slot() {
+        let db = SqliteDatabase::temporary("undo").await;
+
+        apply(&db, 10, &[1]).await;
+        apply(&db, 20, &[2]).await;
+        apply(&db, 30, &[3, 4]).await;
+
+        db.undo(20).await.unwrap();
+
+        assert_eq!(history_slots(&db).await, vec![10]);
+        assert_eq!(block_numbers(&db).await, vec![1]);
+        assert_eq!(db.tip().await.unwrap(), point(10));
+    }
+
+    #[tokio::test]
+    async fn reset_keeps_the_point_and_moves_the_cursor_to_it() {
+        let db = SqliteDatabase::temporary("reset").await;
+
+        apply(&db, 10, &[1]).await;
+        apply(&db, 20, &[2]).await;
+        apply(&db, 30, &[3]).await;
+
+        db.reset(point(20)).await.unwrap();
+
+        assert_eq!(history_slots(&db).await, vec![20, 10]);
+        assert_eq!(block_numbers(&db).await, vec![2, 1]);
+        assert_eq!(db.tip().await.unwrap(), point(20));
+
+        // A point seine never saw, e.g. one Dolos picked from its own history.
+        db.reset(point(15)).await.unwrap();
+
+        assert_eq!(history_slots(&db).await, vec![15, 10]);
+        assert_eq!(block_numbers(&db).await, vec![1]);
+        assert_eq!(db.tip().await.unwrap(), point(15));
+    }
+
+    #[tokio::test]
+    async fn history_is_pruned_to_the_security_parameter() {
+        let db = SqliteDatabase::temporary("prune").await;
+
+        let newest = HISTORY_DEPTH + 50;
+
+        for slot in 1..=newest {
+            apply(&db, slot, &[]).await;
+        }
+
+        let history = history_slots(&db).await;
+
+        assert_eq!(history.len() as u64, HISTORY_DEPTH);
+        assert_eq!(history.first(), Some(&newest));
+        assert_eq!(history.last(), Some(&(newest - HISTORY_DEPTH + 1)));
+
+        let intersect: Vec<u64> = db
+            .intersect()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|point| point.index)
+            .collect();
+
+        // The ten newest points, then sparser ones down to the oldest kept.
+        assert_eq!(&intersect[..10], &history[..10]);
+        assert_eq!(intersect.last(), history.last());
+        assert!(intersect.windows(2).all(|pair| pair[0] > pair[1]));
+        assert!(intersect.len() < 25);
+    }
+
+    #[tokio::test]
+    async fn applying_a_block_again_replaces_it() {
+        let db = SqliteDatabase::temporary("reapply").await;
+
+        apply(&db, 10, &[1]).await;
+        apply(&db, 12, &[1]).await;
+
+        let stored = db.block(1).await.unwrap().unwrap();
+
+        assert_eq!(stored.cardano_slot, 12);
+        assert_eq!(history_slots(&db).await, vec![12, 10]);
+    }
+
+    #[tokio::test]
+    async fn integers_beyond_64_bits_round_trip() {
+        let db = SqliteDatabase::temporary("round-trip").await;
+
+        let big = BigUint::from(u64::MAX) * 1000u32 + 7u32;
+
+        let mut stored = StoredBlock::fixture(42, 100);
+
+        stored.block.target_number = big.clone();
+        stored.block.epoch_time = big.clone() + 1u32;
+
+        db.apply(100, &stored.cardano_hash, std::slice::from_ref(&stored))
+            .await
+            .unwrap();
+
+        let read = db.block(42).await.unwrap().unwrap();
+
+        assert_eq!(read.block.target_number, big);
+        assert_eq!(read.block.epoch_time, big + 1u32);
+    }
+
+    /// The statement in the `block_epoch` migration that backfills `epoch`.
+    fn epoch_backfill(migrations: &[migrations::Migration]) -> String {
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.name == "block_epoch")
+            .unwrap();
+
+        migrations::statements(migration.sql)
+            .into_iter()
+            .find(|statement| statement.starts_with("UPDATE"))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn epoch_backfill_matches_consensus() {
+        let db = SqliteDatabase::temporary("epoch-backfill").await;
+
+        let backfill = epoch_backfill(SQLITE);
+
+        assert_eq!(backfill, epoch_backfill(POSTGRES));
+
+        db.pool
+            .execute(sqlx::raw_sql(
+                r#"
+                    WITH RECURSIVE numbers (number) AS (
+                        SELECT 0 UNION ALL SELECT number + 1 FROM numbers WHERE number < 220000
+                    )
+                    INSERT INTO blocks (
+                        number, hash, leading_zeros, target_number, epoch_time,
+                        current_posix_time, cardano_tx_hash, cardano_slot, cardano_hash
+                    )
+                    SELECT number, '', 0, '0', '0', 0, '', 0, '' FROM numbers
+                "#,
+            ))
+            .await
+            .unwrap();
+
+        db.pool.execute(sqlx::raw_sql(&backfill)).await.unwrap();
+
+        let rows: Vec<(i64, i64)> = sqlx::query_as("SELECT number, epoch FROM blocks")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+
+        assert_eq!(rows.len(), 220001);
+
+        for (number, epoch) in rows {
+            assert_eq!(
+                epoch as u64,
+                consensus::epoch(number as u64),
+                "block {number}"
+            );
+        }
+    }
+}
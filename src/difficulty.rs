@@ -0,0 +1,337 @@
+//! Fortuna's difficulty retarget rules.
+//!
+//! Each block accumulates the time since its predecessor into `epoch_time`.
+//! At the end of an epoch the difficulty is scaled by how far `epoch_time`
+//! was from the target, by at most 4x either way, and `epoch_time` resets.
+
+use miette::bail;
+use num_bigint::{BigInt, BigUint};
+
+use crate::block::TunaBlock;
+
+/// A 16-bit difficulty number is scaled by this much per leading zero.
+const PADDING: u64 = 16;
+
+/// The easiest difficulty the validator allows.
+const MIN_LEADING_ZEROS: u64 = 2;
+const MIN_DIFFICULTY: (u64, u64) = (65535, MIN_LEADING_ZEROS);
+
+/// The hardest difficulty the validator allows.
+const MAX_LEADING_ZEROS: u64 = 62;
+const MAX_DIFFICULTY: (u64, u64) = (4096, MAX_LEADING_ZEROS);
+
+/// How often and towards what the difficulty is retargeted.
+#[derive(Debug, Clone, Copy)]
+pub struct Retarget {
+    /// Blocks per epoch.
+    pub epoch_length: u64,
+    /// How long an epoch should take, in milliseconds.
+    pub epoch_target: u64,
+}
+
+/// The difficulty and epoch time a block mined on top of another must carry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextDifficulty {
+    pub leading_zeros: u64,
+    pub target_number: BigUint,
+    pub epoch_time: BigUint,
+}
+
+impl Retarget {
+    /// Whether the block after `number` starts a new epoch.
+    pub fn retargets_after(&self, number: u64) -> bool {
+        number > 0 && number.is_multiple_of(self.epoch_length)
+    }
+
+    /// The factor, as numerator and denominator, to scale the difficulty by
+    /// after an epoch that took `epoch_time` milliseconds. Clamped to 4x,
+    /// except that, as in the validator, a whole-number ratio isn't clamped.
+    pub fn adjustment(&self, epoch_time: &BigUint) -> (BigUint, BigUint) {
+        let target = BigUint::from(self.epoch_target);
+        let zero = BigUint::ZERO;
+
+        if *epoch_time == zero {
+            return (BigUint::from(1u8), BigUint::from(4u8));
+        }
+
+        if &target / epoch_time >= BigUint::from(4u8) && &target % epoch_time > zero {
+            (BigUint::from(1u8), BigUint::from(4u8))
+        } else if epoch_time / &target >= BigUint::from(4u8) && epoch_time % &target > zero {
+            (BigUint::from(4u8), BigUint::from(1u8))
+        } else {
+            (epoch_time.clone(), target)
+        }
+    }
+
+    /// What `prev`'s successor, mined at `posix_time` (milliseconds), must carry.
+    pub fn next(&self, prev: &TunaBlock, posix_time: u64) -> miette::Result<NextDifficulty> {
+        let epoch_time = BigInt::from(prev.epoch_time.clone()) + BigInt::from(posix_time)
+            - BigInt::from(prev.current_posix_time);
+
+        let Some(epoch_time) = epoch_time.to_biguint() else {
+            bail!(
+                "block {} is timed before block {}",
+                prev.number + 1,
+                prev.number
+            );
+        };
+
+        if !self.retargets_after(prev.number) {
+            return Ok(NextDifficulty {
+                leading_zeros: prev.leading_zeros,
+                target_number: prev.target_number.clone(),
+                epoch_time,
+            });
+        }
+
+        let (numerator, denominator) = self.adjustment(&epoch_time);
+
+        let (target_number, leading_zeros) = scale_difficulty(
+            &numerator,
+            &denominator,
+            &prev.target_number,
+            prev.leading_zeros,
+        );
+
+        Ok(NextDifficulty {
+            leading_zeros,
+            target_number,
+            epoch_time: BigUint::ZERO,
+        })
+    }
+
+    /// Check that `next` carries the difficulty and epoch time the rules
+    /// derive from `prev`.
+    pub fn verify(&self, prev: &TunaBlock, next: &TunaBlock) -> miette::Result<()> {
+        let expected = self.next(prev, next.current_posix_time)?;
+
+        let actual = NextDifficulty {
+            leading_zeros: next.leading_zeros,
+            target_number: next.target_number.clone(),
+            epoch_time: next.epoch_time.clone(),
+        };
+
+        if actual != expected {
+            bail!(
+                "block {} has leading zeros {}, target {} and epoch time {}, \
+                 expected {}, {} and {}",
+                next.number,
+                actual.leading_zeros,
+                actual.target_number,
+                actual.epoch_time,
+                expected.leading_zeros,
+                expected.target_number,
+                expected.epoch_time
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Scale a difficulty by `numerator / denominator`, moving a leading zero in
+/// or out whenever the 16-bit difficulty number under- or overflows.
+pub fn scale_difficulty(
+    numerator: &BigUint,
+    denominator: &BigUint,
+    target_number: &BigUint,
+    leading_zeros: u64,
+) -> (BigUint, u64) {
+    let padding = BigUint::from(PADDING);
+    let limit = BigUint::from(65536u32);
+
+    let padded = target_number * &padding * numerator / denominator;
+    let unpadded = &padded / &padding;
+
+    if &padded / &limit == BigUint::ZERO {
+        if leading_zeros >= MAX_LEADING_ZEROS {
+            (MAX_DIFFICULTY.0.into(), MAX_DIFFICULTY.1)
+        } else {
+            (padded, leading_zeros + 1)
+        }
+    } else if &unpadded / &limit > BigUint::ZERO {
+        if leading_zeros <= MIN_LEADING_ZEROS {
+            (MIN_DIFFICULTY.0.into(), MIN_DIFFICULTY.1)
+        } else {
+            (unpadded / padding, leading_zeros - 1)
+        }
+    } else {
+        (unpadded, leading_zeros)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus;
+
+    fn scale(numerator: u64, denominator: u64, target_number: u64, lz: u64) -> (u64, u64) {
+        let (target_number, lz) = scale_difficulty(
+            &numerator.into(),
+            &denominator.into(),
+            &target_number.into(),
+            lz,
+        );
+
+        (u64::try_from(target_number).unwrap(), lz)
+    }
+
+    fn adjustment(retarget: &Retarget, epoch_time: u64) -> (u64, u64) {
+        let (numerator, denominator) = retarget.adjustment(&epoch_time.into());
+
+        (
+            u64::try_from(numerator).unwrap(),
+            u64::try_from(denominator).unwrap(),
+        )
+    }
+
+    fn block(number: u64, lz: u64, target: u64, epoch_time: u64, posix_time: u64) -> TunaBlock {
+        TunaBlock {
+            leading_zeros: lz,
+            target_number: target.into(),
+            epoch_time: epoch_time.into(),
+            current_posix_time: posix_time,
+            ..TunaBlock::fixture(number)
+        }
+    }
+
+    #[test]
+    fn adjustment_is_clamped_to_4x_harder() {
+        let v1 = consensus::V1.retarget;
+        let target = v1.epoch_target;
+
+        assert_eq!(adjustment(&v1, 0), (1, 4));
+        assert_eq!(adjustment(&v1, target / 5 + 1), (1, 4));
+        assert_eq!(adjustment(&v1, target / 4 - 1), (1, 4));
+        // Like the validator, only clamps when the ratio isn't a whole number.
+        assert_eq!(adjustment(&v1, target / 4), (target / 4, target));
+        assert_eq!(adjustment(&v1, target / 5), (target / 5, target));
+    }
+
+    #[test]
+    fn adjustment_is_clamped_to_4x_easier() {
+        let v2 = consensus::V2.retarget;
+        let target = v2.epoch_target;
+
+        assert_eq!(adjustment(&v2, target * 4 + 1), (4, 1));
+        assert_eq!(adjustment(&v2, target * 5 + 1), (4, 1));
+        // Like the validator, only clamps when the ratio isn't a whole number.
+        assert_eq!(adjustment(&v2, target * 4), (target * 4, target));
+        assert_eq!(adjustment(&v2, target * 5), (target * 5, target));
+    }
+
+    #[test]
+    fn adjustment_is_proportional_within_the_clamp() {
+        let v1 = consensus::V1.retarget;
+        let target = v1.epoch_target;
+
+        assert_eq!(adjustment(&v1, target * 2), (target * 2, target));
+        assert_eq!(adjustment(&v1, target), (target, target));
+    }
+
+    #[test]
+    fn scaling_within_the_target_number_keeps_the_leading_zeros() {
+        assert_eq!(scale(1, 1, 40000, 8), (40000, 8));
+        assert_eq!(scale(1, 4, 40000, 8), (10000, 8));
+        assert_eq!(scale(3, 2, 40000, 8), (60000, 8));
+    }
+
+    #[test]
+    fn underflowing_target_number_borrows_a_leading_zero() {
+        assert_eq!(scale(1, 4, 10000, 8), (40000, 9));
+        assert_eq!(scale(1, 4, 10000, 61), (40000, 62));
+        assert_eq!(scale(1, 4, 10000, 62), MAX_DIFFICULTY);
+    }
+
+    #[test]
+    fn overflowing_target_number_carries_a_leading_zero() {
+        assert_eq!(scale(4, 1, 40000, 8), (10000, 7));
+        assert_eq!(scale(4, 1, 40000, 3), (10000, 2));
+        assert_eq!(scale(4, 1, 40000, 2), MIN_DIFFICULTY);
+    }
+
+    #[test]
+    fn epochs_are_2016_blocks_in_v1_and_504_in_v2() {
+        let (v1, v2) = (consensus::V1.retarget, consensus::V2.retarget);
+
+        assert!(!v1.retargets_after(0));
+        assert!(!v1.retargets_after(2015));
+        assert!(v1.retargets_after(2016));
+        assert!(!v1.retargets_after(2520));
+
+        assert!(v1.retargets_after(consensus::HARD_FORK_BLOCK - 1));
+        assert!(v2.retargets_after(consensus::HARD_FORK_BLOCK - 1));
+        assert!(!v2.retargets_after(30743));
+        assert!(v2.retargets_after(30744));
+    }
+
+    #[test]
+    fn epoch_time_accumulates_between_retargets() {
+        let v1 = consensus::V1.retarget;
+        let prev = block(2015, 8, 40000, 1_000, 10_000);
+
+        let next = v1.next(&prev, 70_000).unwrap();
+
+        assert_eq!(next.leading_zeros, 8);
+        assert_eq!(next.target_number, 40000u32.into());
+        assert_eq!(next.epoch_time, 61_000u32.into());
+    }
+
+    #[test]
+    fn retarget_scales_the_difficulty_and_resets_epoch_time() {
+        let v1 = consensus::V1.retarget;
+
+        // The epoch took half its target, so mining gets twice as hard.
+        let prev = block(2016, 8, 40000, v1.epoch_target / 2 - 600_000, 10_000);
+
+        let next = v1.next(&prev, 610_000).unwrap();
+
+        assert_eq!(
+            next,
+            NextDifficulty {
+                leading_zeros: 8,
+                target_number: 20000u32.into(),
+                epoch_time: BigUint::ZERO,
+            }
+        );
+
+        let mut mined = block(2017, 8, 20000, 0, 610_000);
+
+        assert!(v1.verify(&prev, &mined).is_ok());
+
+        mined.target_number = 40000u32.into();
+
+        assert!(v1.verify(&prev, &mined).is_err());
+    }
+
+    #[test]
+    fn first_v2_retarget_uses_v2_epoch_target() {
+        let (v1, v2) = (consensus::V1.retarget, consensus::V2.retarget);
+
+        // The first V2 epoch took over four times as long as it should.
+        let prev = block(30744, 8, 40000, v2.epoch_target * 4 + 1_000, 0);
+
+        let next = v2.next(&prev, 0).unwrap();
+
+        assert_eq!(
+            (next.target_number, next.leading_zeros),
+            (10000u32.into(), 7)
+        );
+
+        // V1 rules wouldn't retarget here at all.
+        let next = v1.next(&prev, 0).unwrap();
+
+        assert_eq!(
+            (next.target_number, next.leading_zeros),
+            (40000u32.into(), 8)
+        );
+    }
+
+    #[test]
+    fn blocks_timed_before_their_predecessor_are_rejected() {
+        let v2 = consensus::V2.retarget;
+        let prev = block(30300, 8, 40000, 0, 10_000);
+
+        assert!(v2.next(&prev, 9_999).is_err());
+    }
+}
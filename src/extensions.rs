@@ -6,7 +6,7 @@ use utxorpc::{
     ChainBlock,
 };
 
-use crate::constants::{TUNA_V1_ADDRESS, TUNA_V1_POLICY_ID, TUNA_V2_ADDRESS, TUNA_V2_POLICY_ID};
+use crate::consensus::{V1, V2};
 
 pub trait BlockExtensions {
     fn parts(self) -> (BlockHeader, BlockBody);
@@ -24,9 +24,11 @@ pub trait BlockBodyExtensions {
     fn outputs(self) -> impl Iterator<Item = TunaOutput>;
 }
 
+/// A transaction producing a Fortuna state output: its hash, the state
+/// output, its inputs and its remaining outputs.
 pub enum TunaOutput {
-    V1(String, TxOutput, Vec<TxInput>),
-    V2(String, TxOutput, Vec<TxInput>),
+    V1(String, TxOutput, Vec<TxInput>, Vec<TxOutput>),
+    V2(String, TxOutput, Vec<TxInput>, Vec<TxOutput>),
 }
 
 impl BlockBodyExtensions for BlockBody {
@@ -34,18 +36,18 @@ impl BlockBodyExtensions for BlockBody {
         self.tx
             .into_iter()
             .map(|tx| (hex::encode(tx.hash), tx.outputs, tx.inputs))
-            .filter_map(|(tx_hash, outputs, inputs)| {
-                for output in outputs {
-                    if output.is_tuna_v2() {
-                        return Some(TunaOutput::V2(tx_hash, output, inputs));
-                    }
-
-                    if output.is_tuna_v1() {
-                        return Some(TunaOutput::V1(tx_hash, output, inputs));
-                    }
-                }
+            .filter_map(|(tx_hash, mut outputs, inputs)| {
+                let index = outputs
+                    .iter()
+                    .position(|output| output.is_tuna_v2() || output.is_tuna_v1())?;
+
+                let output = outputs.remove(index);
 
-                None
+                if output.is_tuna_v2() {
+                    Some(TunaOutput::V2(tx_hash, output, inputs, outputs))
+                } else {
+                    Some(TunaOutput::V1(tx_hash, output, inputs, outputs))
+                }
             })
     }
 }
@@ -77,36 +79,85 @@ pub trait TxOutputExtensions {
 
     fn is_tuna_v1(&self) -> bool;
 
-    fn datum(self) -> PlutusData;
+    /// Whether this output receives the TUNA minted for a V1 block.
+    fn receives_tuna_v1(&self) -> bool;
+
+    /// The payment key hash of a Shelley address, hex encoded.
+    fn payment_key_hash(&self) -> Option<String>;
+
+    fn datum(self) -> miette::Result<PlutusData>;
 }
 
 impl TxOutputExtensions for TxOutput {
     fn is_tuna_v2(&self) -> bool {
-        self.address == TUNA_V2_ADDRESS
-            && is_lord_tuna(&self.assets, TUNA_V2_POLICY_ID, |asset| {
+        self.address == V2.address
+            && is_lord_tuna(&self.assets, V2.policy_id, |asset| {
                 asset.name.slice(0..4) == "TUNA".as_bytes() && asset.name.len() == 32
             })
     }
 
     fn is_tuna_v1(&self) -> bool {
-        self.address == TUNA_V1_ADDRESS
-            && is_lord_tuna(&self.assets, TUNA_V1_POLICY_ID, |asset| {
+        self.address == V1.address
+            && is_lord_tuna(&self.assets, V1.policy_id, |asset| {
                 asset.name == "lord tuna".as_bytes()
             })
     }
 
-    fn datum(self) -> PlutusData {
-        self.datum.unwrap().payload.unwrap().plutus_data.unwrap()
+    fn receives_tuna_v1(&self) -> bool {
+        self.address != V1.address
+            && is_lord_tuna(&self.assets, V1.policy_id, |asset| {
+                asset.name == "TUNA".as_bytes()
+            })
+    }
+
+    fn payment_key_hash(&self) -> Option<String> {
+        // Shelley address types 0, 2, 4 and 6 start with a key hash payment
+        // credential; odd types start with a script hash.
+        let kind = self.address.first()? >> 4;
+
+        if kind > 7 || kind % 2 == 1 || self.address.len() < 29 {
+            return None;
+        }
+
+        Some(hex::encode(&self.address[1..29]))
+    }
+
+    fn datum(self) -> miette::Result<PlutusData> {
+        match self.datum.and_then(|datum| datum.payload?.plutus_data) {
+            Some(data) => Ok(data),
+            None => miette::bail!("output has no inline datum"),
+        }
     }
 }
 
+/// The miner of a V1 block, from the outputs of the transaction minting it.
+///
+/// V1 redeemers don't name the miner, but the reward is minted to them. Only
+/// outputs receiving TUNA at a key hash address count, since the miner signs
+/// with a key; change usually goes back to that same key. If those outputs
+/// belong to more than one key, e.g. a pool paying out its members, the miner
+/// is left unknown rather than guessed.
+pub fn v1_miner(outputs: &[TxOutput]) -> Option<String> {
+    let mut keys = outputs
+        .iter()
+        .filter(|output| output.receives_tuna_v1())
+        .filter_map(|output| output.payment_key_hash());
+
+    let miner = keys.next()?;
+
+    keys.all(|key| key == miner).then_some(miner)
+}
+
 pub trait RedeemerExtensions {
-    fn plutus_data(self) -> PlutusData;
+    fn plutus_data(self) -> miette::Result<PlutusData>;
 }
 
 impl RedeemerExtensions for Redeemer {
-    fn plutus_data(self) -> PlutusData {
-        self.payload.unwrap().plutus_data.unwrap()
+    fn plutus_data(self) -> miette::Result<PlutusData> {
+        match self.payload.and_then(|payload| payload.plutus_data) {
+            Some(data) => Ok(data),
+            None => miette::bail!("redeemer has no payload"),
+        }
     }
 }
 
@@ -119,3 +170,112 @@ where
         multi_asset.policy_id == policy_id && multi_asset.assets.iter().any(filter)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A base address (type 0) paying to key hash `key`.
+    fn key_address(key: u8) -> Vec<u8> {
+        [vec![0x01], vec![key; 28], vec![0xee; 28]].concat()
+    }
+
+    /// A base address (type 1) paying to script hash `script`.
+    fn script_address(script: u8) -> Vec<u8> {
+        [vec![0x11], vec![script; 28], vec![0xee; 28]].concat()
+    }
+
+    fn output(address: Vec<u8>, asset: Option<&str>) -> TxOutput {
+        TxOutput {
+            address: address.into(),
+            assets: asset
+                .map(|name| Multiasset {
+                    policy_id: V1.policy_id.to_vec().into(),
+                    assets: vec![Asset {
+                        name: name.as_bytes().to_vec().into(),
+                        ..Default::default()
+                    }],
+                })
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn payment_key_hash_only_reads_key_credentials() {
+        for header in [0x01, 0x21, 0x41, 0x61] {
+            let mut address = key_address(0xab);
+            address[0] = header;
+
+            assert_eq!(
+                output(address, None).payment_key_hash(),
+                Some("ab".repeat(28)),
+                "header {header:#x}"
+            );
+        }
+
+        for header in [0x11, 0x31, 0x51, 0x71, 0x82] {
+            let mut address = key_address(0xab);
+            address[0] = header;
+
+            assert_eq!(output(address, None).payment_key_hash(), None);
+        }
+
+        assert_eq!(output(vec![0x61, 0xab], None).payment_key_hash(), None);
+    }
+
+    #[test]
+    fn v1_miner_is_the_key_receiving_tuna() {
+        let outputs = [
+            output(key_address(0x01), None),
+            output(key_address(0xab), Some("TUNA")),
+        ];
+
+        assert_eq!(v1_miner(&outputs), Some("ab".repeat(28)));
+    }
+
+    #[test]
+    fn v1_miner_skips_script_outputs() {
+        let outputs = [
+            output(script_address(0x55), Some("TUNA")),
+            output(key_address(0xab), Some("TUNA")),
+        ];
+
+        assert_eq!(v1_miner(&outputs), Some("ab".repeat(28)));
+        assert_eq!(v1_miner(&outputs[..1]), None);
+    }
+
+    #[test]
+    fn v1_miner_ignores_the_state_output_and_other_assets() {
+        let outputs = [
+            output(V1.address.to_vec(), Some("TUNA")),
+            output(key_address(0x01), Some("lord tuna")),
+        ];
+
+        assert_eq!(v1_miner(&outputs), None);
+    }
+
+    #[test]
+    fn v1_miner_accepts_change_to_the_same_key() {
+        let mut change = key_address(0xab);
+        change[29..].fill(0x01);
+
+        let outputs = [
+            output(key_address(0xab), Some("TUNA")),
+            output(change, Some("TUNA")),
+        ];
+
+        assert_eq!(v1_miner(&outputs), Some("ab".repeat(28)));
+    }
+
+    #[test]
+    fn v1_miner_is_unknown_when_several_keys_receive_tuna() {
+        let outputs = [
+            output(key_address(0xab), Some("TUNA")),
+            output(key_address(0xcd), Some("TUNA")),
+        ];
+
+        assert_eq!(v1_miner(&outputs), None);
+    }
+}
@@ -0,0 +1,64 @@
+use std::num::NonZeroUsize;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::{config::env_or, database::StoredBlock};
+
+/// Events a subscriber may fall behind by when `FEED_CAPACITY` is unset.
+const DEFAULT_CAPACITY: NonZeroUsize = NonZeroUsize::new(256).unwrap();
+
+/// A change to the indexed chain, as pushed to live subscribers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeedEvent {
+    /// A Fortuna block was applied.
+    Block(Box<StoredBlock>),
+    /// Every block at or after `slot` was rolled back.
+    Undo { slot: u64, hash: String },
+    /// Every block after `slot` was discarded and syncing restarts there.
+    Reset { slot: u64, hash: String },
+}
+
+impl FeedEvent {
+    /// The SSE event name for this event.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FeedEvent::Block(_) => "block",
+            FeedEvent::Undo { .. } => "undo",
+            FeedEvent::Reset { .. } => "reset",
+        }
+    }
+}
+
+/// Fans sync events out to every connected subscriber.
+///
+/// Subscribers that fall more than the channel capacity behind skip the
+/// events they missed rather than holding up the sync loop.
+#[derive(Clone)]
+pub struct Feed {
+    sender: broadcast::Sender<FeedEvent>,
+}
+
+impl Feed {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.get());
+
+        Self { sender }
+    }
+
+    /// A feed holding `FEED_CAPACITY` events (default 256), which must be at
+    /// least 1.
+    pub fn from_env() -> miette::Result<Self> {
+        Ok(Self::new(env_or("FEED_CAPACITY", DEFAULT_CAPACITY)?))
+    }
+
+    /// Push `event` to the current subscribers, if there are any.
+    pub fn publish(&self, event: FeedEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<FeedEvent> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::constants::slot_to_posix_time;
+
+/// Sync status shared between the indexer loop and the HTTP endpoints.
+#[derive(Debug)]
+pub struct Health {
+    connected: AtomicBool,
+    /// Whether the indexer's last store call succeeded.
+    store_reachable: AtomicBool,
+    last_slot: AtomicU64,
+    /// Unix time, in seconds, at which the indexer last made progress.
+    last_progress: AtomicU64,
+    max_lag: Duration,
+    stall_timeout: Duration,
+}
+
+impl Health {
+    /// `max_lag` is how far the last processed slot may trail wall-clock time
+    /// before the indexer is reported as not ready, and `stall_timeout` how
+    /// long it may go without progress before it is reported as not live.
+    pub fn new(max_lag: Duration, stall_timeout: Duration) -> Self {
+        Self {
+            connected: AtomicBool::new(false),
+            store_reachable: AtomicBool::new(false),
+            last_slot: AtomicU64::new(0),
+            last_progress: AtomicU64::new(now()),
+            max_lag,
+            stall_timeout,
+        }
+    }
+
+    /// Record that the indexer is still moving: it received an event, or
+    /// started a connection attempt.
+    pub fn set_progress(&self) {
+        self.last_progress.store(now(), Ordering::Relaxed);
+    }
+
+    /// Whether the indexer made progress within the stall timeout. A hung
+    /// sync loop stops making progress, while a reconnecting one doesn't.
+    pub fn is_live(&self) -> bool {
+        now().saturating_sub(self.last_progress.load(Ordering::Relaxed))
+            <= self.stall_timeout.as_secs()
+    }
+
+    pub fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    pub fn set_store_reachable(&self, reachable: bool) {
+        self.store_reachable.store(reachable, Ordering::Relaxed);
+    }
+
+    pub fn is_store_reachable(&self) -> bool {
+        self.store_reachable.load(Ordering::Relaxed)
+    }
+
+    pub fn set_last_slot(&self, slot: u64) {
+        self.last_slot.store(slot, Ordering::Relaxed);
+    }
+
+    /// Seconds between wall-clock time and the last processed slot, or `None`
+    /// before the first block has been processed.
+    pub fn lag_seconds(&self) -> Option<i64> {
+        match self.last_slot.load(Ordering::Relaxed) {
+            0 => None,
+            slot => Some(chrono::Utc::now().timestamp() - slot_to_posix_time(slot) as i64),
+        }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.lag_seconds()
+            .is_some_and(|lag| lag <= self.max_lag.as_secs() as i64)
+    }
+}
+
+fn now() -> u64 {
+    chrono::Utc::now().timestamp() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn live_until_the_stall_timeout_passes() {
+        let health = Health::new(Duration::from_secs(600), Duration::from_secs(60));
+
+        assert!(health.is_live());
+
+        health.last_progress.store(now() - 61, Ordering::Relaxed);
+
+        assert!(!health.is_live());
+
+        health.set_progress();
+
+        assert!(health.is_live());
+    }
+
+    #[test]
+    fn not_synced_before_the_first_block() {
+        let health = Health::new(Duration::from_secs(600), Duration::from_secs(60));
+
+        assert_eq!(health.lag_seconds(), None);
+        assert!(!health.is_synced());
+
+        health.set_last_slot(crate::constants::SHELLEY_START_SLOT);
+
+        assert!(!health.is_synced());
+    }
+}
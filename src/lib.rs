@@ -1,5 +1,19 @@
+pub mod api;
 pub mod block;
+pub mod config;
+pub mod consensus;
 pub mod constants;
 pub mod database;
-pub mod discord;
+pub mod difficulty;
 pub mod extensions;
+pub mod feed;
+pub mod health;
+pub mod logging;
+pub mod metrics;
+pub mod notify;
+pub mod plutus;
+pub mod pow;
+pub mod reconnect;
+pub mod redeemer;
+pub mod retry;
+pub mod server;
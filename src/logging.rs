@@ -0,0 +1,24 @@
+use tracing_subscriber::EnvFilter;
+
+use crate::config::env_or;
+
+/// Install the global tracing subscriber.
+///
+/// The level is taken from `RUST_LOG` (default `info`). Set `LOG_FORMAT=json`
+/// for one JSON object per line, including the active spans.
+pub fn init() -> miette::Result<()> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match env_or("LOG_FORMAT", String::from("text"))?.as_str() {
+        "json" => builder
+            .json()
+            .try_init()
+            .map_err(|error| miette::miette!(error)),
+        "text" => builder.try_init().map_err(|error| miette::miette!(error)),
+        format => miette::bail!("invalid LOG_FORMAT: {format}"),
+    }?;
+
+    Ok(())
+}
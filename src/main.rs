@@ -1,189 +1,586 @@
-use std::env;
+use std::{convert::Infallible, env, sync::Arc, time::Duration};
 
 use miette::IntoDiagnostic;
-use utxorpc::{spec::cardano::plutus_data::PlutusData, CardanoSyncClient, ClientBuilder, TipEvent};
-
-use seine::{block::TunaBlock, database::Database, discord, extensions::*};
+use prometheus::IntCounter;
+use tokio::net::TcpListener;
+use tracing::{debug, field, info, info_span, instrument, warn};
+use utxorpc::{
+    spec::{
+        cardano::{BlockBody, BlockHeader, TxInput, TxOutput},
+        sync::BlockRef,
+    },
+    CardanoSyncClient, ClientBuilder, TipEvent,
+};
+
+use seine::{
+    block::TunaBlock,
+    config::env_or,
+    consensus,
+    constants::slot_to_posix_time,
+    database::{BlockStore, Database, PostgresDatabase, SqliteDatabase, StoredBlock},
+    difficulty::Retarget,
+    extensions::*,
+    feed::{Feed, FeedEvent},
+    health::Health,
+    logging, metrics,
+    notify::Dispatcher,
+    plutus::Field,
+    pow::{self, Miner, PowMode, Verification},
+    reconnect::{self, SyncError},
+    redeemer::V2Redeemer,
+    retry::RetryPolicy,
+    server::{self, AppState},
+};
 
 #[tokio::main]
 async fn main() -> miette::Result<()> {
     let _ = dotenvy::dotenv().ok();
 
+    logging::init()?;
+
+    let migrate_only = match env::args().nth(1).as_deref() {
+        None => false,
+        Some("migrate") => true,
+        Some(command) => miette::bail!("unknown command {command}"),
+    };
+
+    let db: Arc<dyn BlockStore> = match env::var("DATABASE_URL") {
+        Ok(url) if url.starts_with("postgres:") || url.starts_with("postgresql:") => {
+            Arc::new(PostgresDatabase::connect(&url).await?)
+        }
+        Ok(url) => Arc::new(SqliteDatabase::connect(&url).await?),
+        Err(_) => {
+            let account_id = env::var("CLOUDFLARE_ACCOUNT_ID").into_diagnostic()?;
+            let database_id = env::var("CLOUDFLARE_DATABASE_ID").into_diagnostic()?;
+            let d1_token = env::var("CLOUDFLARE_D1_TOKEN").into_diagnostic()?;
+
+            Arc::new(Database::new(account_id, database_id, d1_token))
+        }
+    };
+
+    db.migrate().await?;
+
+    if migrate_only {
+        return Ok(());
+    }
+
     let dolos_endpoint = env::var("DOLOS_ENDPOINT").into_diagnostic()?;
     let dolos_token = env::var("DOLOS_TOKEN").into_diagnostic()?;
-    let account_id = env::var("CLOUDFLARE_ACCOUNT_ID").into_diagnostic()?;
-    let database_id = env::var("CLOUDFLARE_DATABASE_ID").into_diagnostic()?;
-    let d1_token = env::var("CLOUDFLARE_D1_TOKEN").into_diagnostic()?;
-    let discord_webhook_url = env::var("DISCORD_WEBHOOK_URL").into_diagnostic()?;
+    let notifier = Dispatcher::from_env().await?;
+
+    let policy = reconnect::policy_from_env()?;
+
+    let health = Arc::new(Health::new(
+        Duration::from_secs(env_or("READY_MAX_LAG_SECS", 600)?),
+        Duration::from_secs(env_or("LIVENESS_TIMEOUT_SECS", 600)?),
+    ));
+
+    let feed = Feed::from_env()?;
+
+    let context = Context {
+        db: db.clone(),
+        notifier,
+        health: health.clone(),
+        feed: feed.clone(),
+        pow_mode: env_or("POW_VERIFY", PowMode::Warn)?,
+    };
+
+    metrics::register();
+
+    let port: u16 = env_or("PORT", 8080)?;
+
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .await
+        .into_diagnostic()?;
+
+    info!(port, "serving http");
 
-    let db = Database::new(account_id, database_id, d1_token);
+    let state = AppState {
+        store: db,
+        health,
+        feed,
+    };
+
+    let server = tokio::spawn(server::serve(listener, state));
+
+    // Without the server there are no health checks, so stop and let the
+    // supervisor restart the whole process.
+    tokio::select! {
+        Err(error) = sync(&dolos_endpoint, &dolos_token, &context, &policy) => Err(error),
+        result = server => match result {
+            Ok(Ok(())) => miette::bail!("http server stopped"),
+            Ok(Err(error)) => Err(error.wrap_err("http server failed")),
+            Err(error) => Err(miette::miette!("http server panicked: {error}")),
+        },
+    }
+}
+
+/// Follow the chain, reconnecting according to `policy`, until an error that
+/// reconnecting can't fix.
+async fn sync(
+    dolos_endpoint: &str,
+    dolos_token: &str,
+    context: &Context,
+    policy: &RetryPolicy,
+) -> miette::Result<Infallible> {
+    let mut attempt = 0;
 
     loop {
-        println!("connecting");
-
-        let mut client = ClientBuilder::new()
-            .uri(&dolos_endpoint)
-            .into_diagnostic()?
-            .metadata("dmtr-api-key", &dolos_token)
-            .into_diagnostic()?
-            .build::<CardanoSyncClient>()
-            .await;
-
-        println!("connected");
-
-        let intersect = db.tip().await?;
-
-        let mut tip = client.follow_tip(vec![intersect]).await.into_diagnostic()?;
-
-        while let Ok(event) = tip.event().await {
-            match event {
-                TipEvent::Apply(block) => {
-                    let (header, body) = block.parts();
-
-                    for tuna in body.outputs() {
-                        match tuna {
-                            TunaOutput::V1(tx_hash, output, inputs) => {
-                                let mut next_tuna_datum: TunaBlock = output.datum().try_into()?;
-
-                                let prev_block_info = inputs
-                                    .iter()
-                                    .filter(|input| input.is_tuna_v1())
-                                    .find_map(|input| {
-                                        input.redeemer.as_ref().map(|r| {
-                                            // Update previous block info with nonce
-                                            (next_tuna_datum.number - 1, r.clone())
-                                        })
-                                    });
-
-                                if let Some((_block_number, redeemer)) = prev_block_info {
-                                    let PlutusData::Constr(constr) = redeemer.plutus_data() else {
-                                        miette::bail!("failed to decode tuna state");
-                                    };
-
-                                    let nonce =
-                                        constr.fields[0].plutus_data.as_ref().and_then(|data| {
-                                            match data {
-                                                PlutusData::BoundedBytes(b) => Some(hex::encode(b)),
-                                                _ => None,
-                                            }
-                                        });
-
-                                    next_tuna_datum.nonce = nonce;
-                                };
-
-                                let block_hash = hex::encode(&header.hash);
-
-                                let _resp = db
-                                    .apply(&next_tuna_datum, &tx_hash, header.slot, &block_hash)
-                                    .await;
-                            }
-                            TunaOutput::V2(tx_hash, output, inputs) => {
-                                let mut next_tuna_datum: TunaBlock = output.datum().try_into()?;
-
-                                let prev_block_info = inputs
-                                    .iter()
-                                    .filter(|input| input.is_tuna_v2())
-                                    .find_map(|input| {
-                                        input.redeemer.as_ref().map(|r| {
-                                            // Update previous block info with nonce
-                                            (next_tuna_datum.number - 1, r.clone())
-                                        })
-                                    });
-
-                                if let Some((_block_number, redeemer)) = prev_block_info {
-                                    let PlutusData::Constr(constr) = redeemer.plutus_data() else {
-                                        miette::bail!("failed to decode tuna state");
-                                    };
-
-                                    let nonce =
-                                        constr.fields[0].plutus_data.as_ref().and_then(|data| {
-                                            match data {
-                                                PlutusData::BoundedBytes(b) => Some(hex::encode(b)),
-                                                _ => None,
-                                            }
-                                        });
-
-                                    next_tuna_datum.nonce = nonce;
-
-                                    let PlutusData::Constr(miner_cred) =
-                                        constr.fields[1].plutus_data.as_ref().unwrap()
-                                    else {
-                                        todo!()
-                                    };
-
-                                    match miner_cred.tag {
-                                        121 => {
-                                            let PlutusData::BoundedBytes(b) =
-                                                miner_cred.fields[0].plutus_data.as_ref().unwrap()
-                                            else {
-                                                todo!()
-                                            };
-
-                                            let _data = miner_cred.fields[1]
-                                                .plutus_data
-                                                .as_ref()
-                                                .unwrap()
-                                                .clone();
-
-                                            next_tuna_datum.payment_cred = Some(hex::encode(b));
-                                            // next_tuna_datum.data =
-                                            // Some(serde_json::json!(data).to_string());
-                                        }
-                                        122 => {
-                                            let PlutusData::BoundedBytes(policy) =
-                                                miner_cred.fields[0].plutus_data.as_ref().unwrap()
-                                            else {
-                                                unreachable!()
-                                            };
-
-                                            let PlutusData::BoundedBytes(asset_name) =
-                                                miner_cred.fields[1].plutus_data.as_ref().unwrap()
-                                            else {
-                                                unreachable!()
-                                            };
-
-                                            let _data = miner_cred.fields[3]
-                                                .plutus_data
-                                                .as_ref()
-                                                .unwrap()
-                                                .clone();
-
-                                            next_tuna_datum.nft_cred = Some(format!(
-                                                "{}{}",
-                                                hex::encode(policy),
-                                                hex::encode(asset_name)
-                                            ));
-                                        }
-                                        _ => unreachable!(),
-                                    }
-                                };
-
-                                let block_hash = hex::encode(&header.hash);
-
-                                db.apply(&next_tuna_datum, &tx_hash, header.slot, &block_hash)
-                                    .await?;
-
-                                discord::send_webhook(
-                                    &discord_webhook_url,
-                                    &next_tuna_datum,
-                                    &tx_hash,
-                                )
-                                .await?;
-                            }
-                        }
-                    }
-                }
-                TipEvent::Undo(block) => {
-                    let (header, _body) = block.parts();
+        let Err(error) = follow(dolos_endpoint, dolos_token, context, &mut attempt).await;
+
+        context.health.set_connected(false);
+
+        let report = match error {
+            SyncError::Transient(report) => report,
+            SyncError::Fatal(report) => return Err(report),
+        };
+
+        attempt += 1;
+
+        if !policy.should_retry(attempt) {
+            return Err(report.wrap_err(format!("giving up after {attempt} attempts")));
+        }
+
+        let delay = policy.delay(attempt);
+
+        warn!(error = ?report, ?delay, attempt, "disconnected, reconnecting");
+
+        tokio::time::sleep(delay).await;
+    }
+}
 
-                    db.undo(header.slot).await?;
+/// State shared by every sync session.
+struct Context {
+    db: Arc<dyn BlockStore>,
+    notifier: Dispatcher,
+    health: Arc<Health>,
+    feed: Feed,
+    pow_mode: PowMode,
+}
+
+impl Context {
+    /// Record whether a store call got through, for readiness, and treat a
+    /// failure as transient.
+    fn stored<T>(&self, result: miette::Result<T>) -> Result<T, SyncError> {
+        self.health.set_store_reachable(result.is_ok());
+
+        result.map_err(SyncError::Transient)
+    }
+}
+
+/// Follow the chain from the store's intersection points until the stream
+/// ends. Resets `attempt` once the connection delivers an event.
+async fn follow(
+    dolos_endpoint: &str,
+    dolos_token: &str,
+    context: &Context,
+    attempt: &mut u32,
+) -> Result<Infallible, SyncError> {
+    info!(endpoint = dolos_endpoint, "connecting");
+
+    context.health.set_progress();
+
+    let mut client = ClientBuilder::new()
+        .uri(dolos_endpoint)
+        .into_diagnostic()?
+        .metadata("dmtr-api-key", dolos_token)
+        .into_diagnostic()?
+        .build::<CardanoSyncClient>()
+        .await;
+
+    info!("connected");
+
+    let intersect = context.stored(context.db.intersect().await)?;
+
+    let mut tip = client
+        .follow_tip(intersect)
+        .await
+        .map_err(reconnect::intersect_error)?;
+
+    context.health.set_connected(true);
+
+    // Consecutive undone blocks, reported as one rollback on the next apply.
+    let mut rollback_depth = 0;
+
+    loop {
+        let event = tip.event().await.map_err(reconnect::stream_error)?;
+
+        *attempt = 0;
+
+        context.health.set_progress();
+
+        match event {
+            TipEvent::Apply(block) => {
+                if rollback_depth > 0 {
+                    metrics::ROLLBACK_DEPTH.observe(rollback_depth as f64);
+
+                    rollback_depth = 0;
                 }
-                TipEvent::Reset(point) => {
-                    db.reset(point).await?;
+
+                let (header, body) = block.parts();
+
+                apply(context, header, body).await?;
+            }
+            TipEvent::Undo(block) => {
+                if rollback_depth == 0 {
+                    metrics::ROLLBACKS.inc();
                 }
+
+                rollback_depth += 1;
+
+                let (header, _body) = block.parts();
+
+                undo(context, header).await?;
+            }
+            TipEvent::Reset(point) => {
+                reset(context, point).await?;
             }
         }
+    }
+}
+
+#[instrument(skip_all, fields(slot = header.slot, hash = %hex::encode(&header.hash)))]
+async fn apply(context: &Context, header: BlockHeader, body: BlockBody) -> Result<(), SyncError> {
+    let block_hash = hex::encode(&header.hash);
+
+    let (blocks, announcements) = tuna_blocks(header.slot, &block_hash, body, context.pow_mode)?;
+
+    context.stored(context.db.apply(header.slot, &block_hash, &blocks).await)?;
 
-        println!("disconnected");
+    context.health.set_last_slot(header.slot);
+
+    metrics::LAST_SLOT.set(header.slot as i64);
+    metrics::TIP_LAG_SECONDS
+        .set(chrono::Utc::now().timestamp() - slot_to_posix_time(header.slot) as i64);
+
+    if let Some(last) = blocks.iter().map(|stored| stored.block.number).max() {
+        metrics::LAST_TUNA_BLOCK.set(last as i64);
     }
+
+    for stored in &blocks {
+        info!(
+            number = stored.block.number,
+            tx_hash = %stored.cardano_tx_hash,
+            "applied tuna block"
+        );
+
+        context
+            .feed
+            .publish(FeedEvent::Block(Box::new(stored.clone())));
+    }
+
+    for index in announcements {
+        let stored = &blocks[index];
+
+        // Checked first, so catching up doesn't look up every previous block.
+        if !context.notifier.should_announce(stored) {
+            continue;
+        }
+
+        let number = stored.block.number.saturating_sub(1);
+
+        let previous = match blocks
+            .iter()
+            .find(|previous| previous.block.number == number)
+        {
+            Some(previous) => Some(previous.clone()),
+            // Only for context, so not worth failing over.
+            None => context.db.block(number).await.unwrap_or_else(|error| {
+                warn!(?error, "couldn't look up the previous block");
+
+                None
+            }),
+        };
+
+        context.notifier.announce(stored, previous);
+    }
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(slot = header.slot, hash = %hex::encode(&header.hash)))]
+async fn undo(context: &Context, header: BlockHeader) -> Result<(), SyncError> {
+    context.stored(context.db.undo(header.slot).await)?;
+
+    context.notifier.rollback(header.slot).await;
+
+    context.feed.publish(FeedEvent::Undo {
+        slot: header.slot,
+        hash: hex::encode(&header.hash),
+    });
+
+    info!("undid cardano block");
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(slot = point.index, hash = %hex::encode(&point.hash)))]
+async fn reset(context: &Context, point: BlockRef) -> Result<(), SyncError> {
+    let slot = point.index;
+    let hash = hex::encode(&point.hash);
+
+    context.stored(context.db.reset(point).await)?;
+
+    context.health.set_last_slot(slot);
+
+    // Everything after the point is gone.
+    context.notifier.rollback(slot + 1).await;
+
+    context.feed.publish(FeedEvent::Reset { slot, hash });
+
+    info!("reset to cardano block");
+
+    Ok(())
+}
+
+/// Decode the Fortuna blocks minted in a Cardano block, along with the
+/// indices of those that should be announced.
+fn tuna_blocks(
+    slot: u64,
+    block_hash: &str,
+    body: BlockBody,
+    pow_mode: PowMode,
+) -> miette::Result<(Vec<StoredBlock>, Vec<usize>)> {
+    let mut blocks = Vec::new();
+    let mut announcements = Vec::new();
+
+    for tuna in body.outputs() {
+        match tuna {
+            TunaOutput::V1(tx_hash, output, inputs, outputs) => {
+                let span = info_span!(
+                    "tuna_block",
+                    version = 1,
+                    tx_hash = %tx_hash,
+                    number = field::Empty
+                );
+                let _enter = span.enter();
+
+                let next_tuna_datum = match decode_v1(output, &inputs, &outputs) {
+                    Ok(block) => block,
+                    Err(error) => {
+                        skip_undecodable(error);
+
+                        continue;
+                    }
+                };
+
+                span.record("number", next_tuna_datum.number);
+
+                let prev = inputs.iter().find(|input| input.is_tuna_v1());
+
+                verify_block(
+                    pow_mode,
+                    &consensus::V1.retarget,
+                    prev,
+                    &next_tuna_datum,
+                    &Miner::None,
+                )?;
+
+                debug!(
+                    nonce = ?next_tuna_datum.nonce,
+                    payment_cred = ?next_tuna_datum.payment_cred,
+                    "decoded tuna block"
+                );
+
+                blocks.push(StoredBlock {
+                    epoch: consensus::epoch(next_tuna_datum.number),
+                    block: next_tuna_datum,
+                    cardano_tx_hash: tx_hash,
+                    cardano_slot: slot,
+                    cardano_hash: block_hash.to_string(),
+                });
+            }
+            TunaOutput::V2(tx_hash, output, inputs, _outputs) => {
+                let span = info_span!(
+                    "tuna_block",
+                    version = 2,
+                    tx_hash = %tx_hash,
+                    number = field::Empty
+                );
+                let _enter = span.enter();
+
+                let next_tuna_datum = match decode_v2(output, &inputs) {
+                    Ok(block) => block,
+                    Err(error) => {
+                        skip_undecodable(error);
+
+                        continue;
+                    }
+                };
+
+                span.record("number", next_tuna_datum.number);
+
+                let prev = inputs.iter().find(|input| input.is_tuna_v2());
+
+                let miner = match next_tuna_datum
+                    .payment_cred
+                    .as_ref()
+                    .or(next_tuna_datum.nft_cred.as_ref())
+                {
+                    Some(miner) => Miner::Known(hex::decode(miner).into_diagnostic()?),
+                    None => Miner::Unknown,
+                };
+
+                verify_block(
+                    pow_mode,
+                    &consensus::V2.retarget,
+                    prev,
+                    &next_tuna_datum,
+                    &miner,
+                )?;
+
+                debug!(
+                    nonce = ?next_tuna_datum.nonce,
+                    payment_cred = ?next_tuna_datum.payment_cred,
+                    nft_cred = ?next_tuna_datum.nft_cred,
+                    "decoded tuna block"
+                );
+
+                announcements.push(blocks.len());
+
+                blocks.push(StoredBlock {
+                    epoch: consensus::epoch(next_tuna_datum.number),
+                    block: next_tuna_datum,
+                    cardano_tx_hash: tx_hash,
+                    cardano_slot: slot,
+                    cardano_hash: block_hash.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok((blocks, announcements))
+}
+
+/// Decode a V1 block from its state output, the redeemer spending the previous
+/// state, and the outputs receiving the minted TUNA.
+fn decode_v1(
+    output: TxOutput,
+    inputs: &[TxInput],
+    outputs: &[TxOutput],
+) -> miette::Result<TunaBlock> {
+    let mut block: TunaBlock = output.datum()?.try_into()?;
+
+    let redeemer = inputs
+        .iter()
+        .filter(|input| input.is_tuna_v1())
+        .find_map(|input| input.redeemer.clone());
+
+    if let Some(redeemer) = redeemer {
+        let redeemer = redeemer.plutus_data()?;
+        let redeemer = Field::root(&redeemer, "redeemer").constr()?;
+
+        block.nonce = Some(redeemer.field(0, "nonce")?.hex()?);
+    };
+
+    block.payment_cred = v1_miner(outputs);
+
+    Ok(block)
+}
+
+/// Decode a V2 block from its state output and the redeemer spending the
+/// previous state, which names the miner.
+fn decode_v2(output: TxOutput, inputs: &[TxInput]) -> miette::Result<TunaBlock> {
+    let mut block: TunaBlock = output.datum()?.try_into()?;
+
+    let redeemer = inputs
+        .iter()
+        .filter(|input| input.is_tuna_v2())
+        .find_map(|input| input.redeemer.clone());
+
+    if let Some(redeemer) = redeemer {
+        let redeemer = V2Redeemer::decode(&redeemer.plutus_data()?)?;
+
+        block.nonce = Some(redeemer.nonce);
+
+        if let Some(miner) = redeemer.miner {
+            block.payment_cred = miner.payment_cred();
+            block.nft_cred = miner.nft_cred();
+            block.data = Some(miner.data().to_string());
+        }
+    };
+
+    Ok(block)
+}
+
+/// Skip a Fortuna output that can't be decoded, rather than stop syncing on
+/// it. Logged within the output's span and counted.
+fn skip_undecodable(error: miette::Report) {
+    metrics::DECODE_FAILURES.inc();
+
+    warn!(?error, "skipping undecodable tuna block");
+}
+
+/// Check `next` against the state spent by `prev`, if there is one: its
+/// proof of work, and its difficulty under `retarget`. Failures are counted
+/// and logged, and refuse the block in strict mode.
+fn verify_block(
+    mode: PowMode,
+    retarget: &Retarget,
+    prev: Option<&TxInput>,
+    next: &TunaBlock,
+    miner: &Miner,
+) -> miette::Result<()> {
+    if mode == PowMode::Off {
+        return Ok(());
+    }
+
+    let Some(prev) = prev.and_then(|input| input.as_output.clone()) else {
+        return Ok(());
+    };
+
+    let prev = match prev.datum().and_then(TunaBlock::try_from) {
+        Ok(prev) => prev,
+        Err(error) => {
+            return flag(
+                mode,
+                error,
+                &metrics::POW_FAILURES,
+                "failed to decode the spent state",
+            )
+        }
+    };
+
+    match pow::verify(&prev, next, miner) {
+        Ok(Verification::Verified) => {}
+        Ok(Verification::Unverifiable) => {
+            metrics::POW_UNVERIFIABLE.inc();
+
+            debug!("miner credential unknown, proof of work unverifiable");
+        }
+        Err(error) => flag(
+            mode,
+            error,
+            &metrics::POW_FAILURES,
+            "proof of work did not verify",
+        )?,
+    }
+
+    if let Err(error) = retarget.verify(&prev, next) {
+        flag(
+            mode,
+            error,
+            &metrics::RETARGET_FAILURES,
+            "difficulty diverges from the retarget rules",
+        )?;
+    }
+
+    Ok(())
+}
+
+fn flag(
+    mode: PowMode,
+    error: miette::Report,
+    counter: &IntCounter,
+    message: &'static str,
+) -> miette::Result<()> {
+    counter.inc();
+
+    if mode == PowMode::Strict {
+        return Err(error.wrap_err(message));
+    }
+
+    warn!(?error, "{message}");
+
+    Ok(())
 }
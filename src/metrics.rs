@@ -0,0 +1,123 @@
+use std::sync::LazyLock;
+
+use prometheus::{
+    register_histogram, register_int_counter, register_int_gauge, Histogram, IntCounter, IntGauge,
+};
+
+pub static LAST_SLOT: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "seine_last_slot",
+        "Slot of the last processed Cardano block"
+    )
+    .expect("metric can be registered")
+});
+
+pub static LAST_TUNA_BLOCK: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "seine_last_tuna_block",
+        "Number of the last applied Fortuna block"
+    )
+    .expect("metric can be registered")
+});
+
+pub static TIP_LAG_SECONDS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "seine_tip_lag_seconds",
+        "Wall-clock time minus the time of the last processed Cardano block"
+    )
+    .expect("metric can be registered")
+});
+
+pub static ROLLBACKS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("seine_rollbacks_total", "Number of chain rollbacks")
+        .expect("metric can be registered")
+});
+
+pub static ROLLBACK_DEPTH: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "seine_rollback_depth_blocks",
+        "Number of Cardano blocks undone per rollback",
+        vec![1.0, 2.0, 3.0, 5.0, 10.0, 20.0, 50.0, 100.0]
+    )
+    .expect("metric can be registered")
+});
+
+pub static D1_REQUEST_SECONDS: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "seine_d1_request_duration_seconds",
+        "Latency of Cloudflare D1 HTTP requests"
+    )
+    .expect("metric can be registered")
+});
+
+pub static D1_REQUEST_ERRORS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "seine_d1_request_errors_total",
+        "Cloudflare D1 requests that failed or returned an unsuccessful result"
+    )
+    .expect("metric can be registered")
+});
+
+pub static WEBHOOK_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "seine_webhook_failures_total",
+        "Block announcements that could not be delivered"
+    )
+    .expect("metric can be registered")
+});
+
+pub static DECODE_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "seine_decode_failures_total",
+        "Fortuna outputs skipped because their datum or redeemer could not be decoded"
+    )
+    .expect("metric can be registered")
+});
+
+pub static POW_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "seine_pow_verification_failures_total",
+        "Fortuna blocks whose proof of work did not verify"
+    )
+    .expect("metric can be registered")
+});
+
+pub static POW_UNVERIFIABLE: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "seine_pow_unverifiable_total",
+        "Fortuna blocks whose proof of work couldn't be checked because their miner is unknown"
+    )
+    .expect("metric can be registered")
+});
+
+pub static RETARGET_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "seine_retarget_failures_total",
+        "Fortuna blocks whose difficulty or epoch time diverges from the retarget rules"
+    )
+    .expect("metric can be registered")
+});
+
+/// Register every metric up front, so all series are exported from startup
+/// rather than appearing the first time they change.
+pub fn register() {
+    LazyLock::force(&LAST_SLOT);
+    LazyLock::force(&LAST_TUNA_BLOCK);
+    LazyLock::force(&TIP_LAG_SECONDS);
+    LazyLock::force(&ROLLBACKS);
+    LazyLock::force(&ROLLBACK_DEPTH);
+    LazyLock::force(&D1_REQUEST_SECONDS);
+    LazyLock::force(&D1_REQUEST_ERRORS);
+    LazyLock::force(&WEBHOOK_FAILURES);
+    LazyLock::force(&DECODE_FAILURES);
+    LazyLock::force(&POW_FAILURES);
+    LazyLock::force(&POW_UNVERIFIABLE);
+    LazyLock::force(&RETARGET_FAILURES);
+}
+
+/// Render every registered metric in the Prometheus text format.
+pub fn render() -> miette::Result<String> {
+    prometheus::TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .map_err(|error| miette::miette!(error))
+}
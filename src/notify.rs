@@ -0,0 +1,753 @@
+use std::{collections::BTreeMap, env, num::NonZeroUsize, sync::Mutex, time::Duration};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::env_or, constants::slot_to_posix_time, database::StoredBlock, metrics,
+    retry::RetryPolicy,
+};
+
+mod discord;
+mod file;
+mod slack;
+mod telegram;
+mod webhook;
+
+pub use discord::{DiscordNotifier, EmbedField, EmbedFields, EmbedTemplate};
+pub use file::FileNotifier;
+pub use slack::SlackNotifier;
+pub use telegram::TelegramNotifier;
+pub use webhook::WebhookNotifier;
+
+/// How long to wait after a 429 that didn't say how long to wait.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Notifications each sink queues when `NOTIFY_QUEUE_CAPACITY` is unset.
+const DEFAULT_QUEUE_CAPACITY: NonZeroUsize = NonZeroUsize::new(100).unwrap();
+
+/// How many announced blocks are remembered for deduplication and retraction.
+/// Far more than can be rolled back.
+const ANNOUNCED_CAPACITY: usize = 256;
+
+/// A newly mined block, with the one before it for context.
+#[derive(Debug, Clone, Serialize)]
+pub struct Announcement {
+    #[serde(flatten)]
+    pub stored: StoredBlock,
+    /// Left out of JSON, which is shaped like a block from the REST API.
+    #[serde(skip)]
+    pub previous: Option<StoredBlock>,
+}
+
+/// Something to tell the sinks about.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notification {
+    /// A block was mined.
+    Block(Box<Announcement>),
+    /// A block that was announced has been rolled back.
+    Retract(Box<StoredBlock>),
+}
+
+impl Notification {
+    pub fn stored(&self) -> &StoredBlock {
+        match self {
+            Notification::Block(announcement) => &announcement.stored,
+            Notification::Retract(stored) => stored,
+        }
+    }
+}
+
+/// Somewhere newly mined blocks are announced.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Short name used in logs.
+    fn name(&self) -> &'static str;
+
+    async fn notify(&self, notification: &Notification) -> Result<(), NotifyError>;
+}
+
+/// Why a notification could not be delivered.
+#[derive(Debug)]
+pub enum NotifyError {
+    /// The service asked us to wait this long before trying again.
+    RateLimited(Duration),
+    Failed(miette::Report),
+}
+
+impl From<miette::Report> for NotifyError {
+    fn from(report: miette::Report) -> Self {
+        NotifyError::Failed(report)
+    }
+}
+
+/// Fans each announcement out to every configured sink.
+///
+/// Every sink has its own bounded queue and background task, so a slow or
+/// failing service never holds up syncing or the other sinks. Only a
+/// retraction waits for a full queue.
+///
+/// Blocks are only announced once per number, and only near the tip so that
+/// catching up doesn't announce history. Announced blocks that are rolled
+/// back are retracted.
+pub struct Dispatcher {
+    queues: Vec<(&'static str, mpsc::Sender<Notification>)>,
+    /// Announced blocks by number.
+    announced: Mutex<BTreeMap<u64, StoredBlock>>,
+    max_lag: Duration,
+}
+
+impl Dispatcher {
+    /// Enable a sink for each one configured in the environment:
+    ///
+    /// - `DISCORD_WEBHOOK_URL`, checked now if Discord is reachable, and sent
+    ///   a test embed if `DISCORD_SELF_TEST` is `true`
+    /// - `TELEGRAM_BOT_TOKEN` and `TELEGRAM_CHAT_ID`
+    /// - `SLACK_WEBHOOK_URL`
+    /// - `NOTIFY_WEBHOOK_URL`, which receives each block as JSON
+    /// - `NOTIFY_FILE`, which appends each block as a JSON line (`-` for stdout)
+    ///
+    /// Each queue holds `NOTIFY_QUEUE_CAPACITY` (default 100, at least 1)
+    /// announcements, and each is attempted `NOTIFY_MAX_ATTEMPTS` (default 5)
+    /// times. Blocks more than `NOTIFY_MAX_LAG_SECS` (default 600) old aren't
+    /// announced.
+    pub async fn from_env() -> miette::Result<Self> {
+        let mut sinks: Vec<Box<dyn Notifier>> = Vec::new();
+
+        if let Ok(url) = env::var("DISCORD_WEBHOOK_URL") {
+            let discord = DiscordNotifier::connect(url, EmbedTemplate::from_env()?).await?;
+
+            if env_or("DISCORD_SELF_TEST", false)? {
+                discord.self_test().await?;
+            }
+
+            sinks.push(Box::new(discord));
+        }
+
+        match (env::var("TELEGRAM_BOT_TOKEN"), env::var("TELEGRAM_CHAT_ID")) {
+            (Ok(token), Ok(chat_id)) => sinks.push(Box::new(TelegramNotifier::new(token, chat_id))),
+            (Err(_), Err(_)) => {}
+            _ => miette::bail!("TELEGRAM_BOT_TOKEN and TELEGRAM_CHAT_ID must be set together"),
+        }
+
+        if let Ok(url) = env::var("SLACK_WEBHOOK_URL") {
+            sinks.push(Box::new(SlackNotifier::new(url)));
+        }
+
+        if let Ok(url) = env::var("NOTIFY_WEBHOOK_URL") {
+            sinks.push(Box::new(WebhookNotifier::new(url)));
+        }
+
+        if let Ok(path) = env::var("NOTIFY_FILE") {
+            sinks.push(Box::new(FileNotifier::new(path)));
+        }
+
+        let capacity = env_or("NOTIFY_QUEUE_CAPACITY", DEFAULT_QUEUE_CAPACITY)?;
+
+        let policy = RetryPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            max_attempts: Some(env_or("NOTIFY_MAX_ATTEMPTS", 5)?),
+        };
+
+        let names: Vec<_> = sinks.iter().map(|sink| sink.name()).collect();
+
+        info!(sinks = ?names, "notifications enabled");
+
+        let mut dispatcher =
+            Dispatcher::new(Duration::from_secs(env_or("NOTIFY_MAX_LAG_SECS", 600)?));
+
+        for sink in sinks {
+            dispatcher.spawn(sink, capacity, policy.clone());
+        }
+
+        Ok(dispatcher)
+    }
+
+    /// A dispatcher without sinks, announcing blocks at most `max_lag` old.
+    pub fn new(max_lag: Duration) -> Self {
+        Self {
+            queues: Vec::new(),
+            announced: Mutex::new(BTreeMap::new()),
+            max_lag,
+        }
+    }
+
+    /// Deliver to `sink` from a background task.
+    pub fn spawn(&mut self, sink: Box<dyn Notifier>, capacity: NonZeroUsize, policy: RetryPolicy) {
+        let (sender, receiver) = mpsc::channel(capacity.get());
+
+        self.queues.push((sink.name(), sender));
+
+        tokio::spawn(deliver(sink, receiver, policy));
+    }
+
+    /// Whether `stored` would be announced: there are sinks, it is recent
+    /// enough, and it hasn't been announced already. Lets callers skip
+    /// gathering context for blocks that won't be announced.
+    pub fn should_announce(&self, stored: &StoredBlock) -> bool {
+        if !self.is_recent(stored) {
+            return false;
+        }
+
+        let announced = self.announced.lock().expect("announced lock poisoned");
+
+        !announced.contains_key(&stored.block.number)
+    }
+
+    /// Whether there are sinks, and `stored` is recent enough to announce.
+    fn is_recent(&self, stored: &StoredBlock) -> bool {
+        if self.queues.is_empty() {
+            return false;
+        }
+
+        let lag = chrono::Utc::now().timestamp() - slot_to_posix_time(stored.cardano_slot) as i64;
+
+        if lag > self.max_lag.as_secs() as i64 {
+            debug!(
+                number = stored.block.number,
+                lag, "not announcing while catching up"
+            );
+
+            return false;
+        }
+
+        true
+    }
+
+    /// Announce `stored`, which follows `previous`, if
+    /// [`should_announce`](Self::should_announce) allows it.
+    pub fn announce(&self, stored: &StoredBlock, previous: Option<StoredBlock>) {
+        if !self.is_recent(stored) {
+            return;
+        }
+
+        {
+            let mut announced = self.announced.lock().expect("announced lock poisoned");
+
+            // Checked and claimed under one lock, so a block is only ever
+            // announced once.
+            if announced.contains_key(&stored.block.number) {
+                debug!(number = stored.block.number, "already announced");
+
+                return;
+            }
+
+            announced.insert(stored.block.number, stored.clone());
+
+            while announced.len() > ANNOUNCED_CAPACITY {
+                announced.pop_first();
+            }
+        }
+
+        let notification = Notification::Block(Box::new(Announcement {
+            stored: stored.clone(),
+            previous,
+        }));
+
+        for (sink, sender) in &self.queues {
+            if let Err(error) = sender.try_send(notification.clone()) {
+                dead_letter(sink, &notification, &miette::miette!("{error}"));
+            }
+        }
+    }
+
+    /// Retract every announced block indexed at or after `slot`.
+    ///
+    /// Unlike announcements, retractions wait for room in each sink's queue:
+    /// dropping one would leave a rolled back block announced.
+    pub async fn rollback(&self, slot: u64) {
+        let retracted: Vec<StoredBlock> = {
+            let mut announced = self.announced.lock().expect("announced lock poisoned");
+
+            let numbers: Vec<u64> = announced
+                .values()
+                .filter(|stored| stored.cardano_slot >= slot)
+                .map(|stored| stored.block.number)
+                .collect();
+
+            numbers
+                .iter()
+                .filter_map(|number| announced.remove(number))
+                .collect()
+        };
+
+        for stored in retracted {
+            info!(number = stored.block.number, "retracting announcement");
+
+            let notification = Notification::Retract(Box::new(stored));
+
+            for (sink, sender) in &self.queues {
+                // Only fails once the sink's task is gone.
+                if let Err(error) = sender.send(notification.clone()).await {
+                    dead_letter(sink, &notification, &miette::miette!("{error}"));
+                }
+            }
+        }
+    }
+}
+
+async fn deliver(
+    sink: Box<dyn Notifier>,
+    mut receiver: mpsc::Receiver<Notification>,
+    policy: RetryPolicy,
+) {
+    while let Some(notification) = receiver.recv().await {
+        let mut attempt = 0;
+
+        loop {
+            let error = match sink.notify(&notification).await {
+                Ok(()) => break,
+                Err(error) => error,
+            };
+
+            // Rate limits count as attempts too, so a sink that keeps
+            // refusing can't hold up its queue forever.
+            attempt += 1;
+
+            if !policy.should_retry(attempt) {
+                let error = match error {
+                    NotifyError::RateLimited(_) => miette::miette!("still rate limited"),
+                    NotifyError::Failed(error) => error,
+                };
+
+                dead_letter(sink.name(), &notification, &error);
+
+                break;
+            }
+
+            let delay = match error {
+                NotifyError::RateLimited(retry_after) => {
+                    warn!(sink = sink.name(), ?retry_after, attempt, "rate limited");
+
+                    retry_after
+                }
+                NotifyError::Failed(error) => {
+                    let delay = policy.delay(attempt);
+
+                    warn!(
+                        sink = sink.name(),
+                        ?error,
+                        ?delay,
+                        attempt,
+                        "notification failed, retrying"
+                    );
+
+                    delay
+                }
+            };
+
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Give up on sending `notification` to `sink`. Logged in full under the
+/// `dead_letter` target, so it can be routed elsewhere and replayed.
+fn dead_letter(sink: &str, notification: &Notification, error: &miette::Report) {
+    metrics::WEBHOOK_FAILURES.inc();
+
+    error!(
+        target: "dead_letter",
+        sink,
+        number = notification.stored().block.number,
+        notification = %serde_json::to_string(notification).unwrap_or_default(),
+        ?error,
+        "dropped notification"
+    );
+}
+
+/// Fail on an unsuccessful response, turning a 429 into
+/// [`NotifyError::RateLimited`] with its `Retry-After` delay.
+pub fn check_response(response: reqwest::Response) -> Result<reqwest::Response, NotifyError> {
+    if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .unwrap_or(DEFAULT_RETRY_AFTER);
+
+        return Err(NotifyError::RateLimited(retry_after));
+    }
+
+    response.error_for_status().map_err(request_error)
+}
+
+/// A failed request, without its URL: for every sink it holds a secret, the
+/// Telegram bot token or the webhook URL itself.
+pub fn request_error(error: reqwest::Error) -> NotifyError {
+    NotifyError::Failed(miette::miette!(error.without_url()))
+}
+
+/// Link to the transaction that minted a block.
+pub fn explorer_url(cardano_tx_hash: &str) -> String {
+    format!("https://cexplorer.io/tx/{cardano_tx_hash}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, sync::Arc};
+
+    use tokio::sync::Semaphore;
+
+    use super::*;
+    use crate::constants::{SHELLEY_START_POSIX_TIME, SHELLEY_START_SLOT};
+
+    /// Forwards every notification it's given, then waits for a permit from
+    /// `gate` if there is one.
+    struct Forward {
+        sent: mpsc::UnboundedSender<Notification>,
+        gate: Option<Arc<Semaphore>>,
+    }
+
+    #[async_trait]
+    impl Notifier for Forward {
+        fn name(&self) -> &'static str {
+            "forward"
+        }
+
+        async fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+            self.sent.send(notification.clone()).unwrap();
+
+            if let Some(gate) = &self.gate {
+                gate.acquire().await.unwrap().forget();
+            }
+
+            Ok(())
+        }
+    }
+
+    fn dispatcher(
+        capacity: usize,
+        gate: Option<Arc<Semaphore>>,
+    ) -> (Dispatcher, mpsc::UnboundedReceiver<Notification>) {
+        let (sent, received) = mpsc::unbounded_channel();
+        let mut dispatcher = Dispatcher::new(Duration::from_secs(600));
+
+        dispatcher.spawn(
+            Box::new(Forward { sent, gate }),
+            NonZeroUsize::new(capacity).unwrap(),
+            policy(1),
+        );
+
+        (dispatcher, received)
+    }
+
+    /// Everything the sink was sent, once `dispatcher` is dropped and its
+    /// queue drained.
+    async fn drain(
+        dispatcher: Dispatcher,
+        mut received: mpsc::UnboundedReceiver<Notification>,
+    ) -> Vec<(&'static str, u64)> {
+        drop(dispatcher);
+
+        let mut sent = Vec::new();
+
+        while let Some(notification) = received.recv().await {
+            sent.push(summary(&notification));
+        }
+
+        sent
+    }
+
+    fn summary(notification: &Notification) -> (&'static str, u64) {
+        let kind = match notification {
+            Notification::Block(_) => "block",
+            Notification::Retract(_) => "retract",
+        };
+
+        (kind, notification.stored().block.number)
+    }
+
+    /// Block `number`, minted a few minutes ago.
+    fn recent(number: u64) -> StoredBlock {
+        let now = chrono::Utc::now().timestamp() as u64;
+        let slot = now - SHELLEY_START_POSIX_TIME + SHELLEY_START_SLOT;
+
+        StoredBlock::fixture(number, slot - 300 + number)
+    }
+
+    #[tokio::test]
+    async fn should_announce_recent_new_blocks_with_sinks() {
+        assert!(!Dispatcher::new(Duration::from_secs(600)).should_announce(&recent(1)));
+
+        let (dispatcher, received) = dispatcher(10, None);
+
+        assert!(dispatcher.should_announce(&recent(1)));
+        assert!(!dispatcher.should_announce(&StoredBlock::fixture(1, SHELLEY_START_SLOT)));
+
+        dispatcher.announce(&recent(1), None);
+
+        assert!(!dispatcher.should_announce(&recent(1)));
+        assert_eq!(drain(dispatcher, received).await, [("block", 1)]);
+    }
+
+    #[tokio::test]
+    async fn announces_each_block_once() {
+        let (dispatcher, received) = dispatcher(10, None);
+
+        dispatcher.announce(&recent(1), None);
+        dispatcher.announce(&recent(2), Some(recent(1)));
+        dispatcher.announce(&recent(1), None);
+        dispatcher.announce(&StoredBlock::fixture(3, SHELLEY_START_SLOT), None);
+
+        assert_eq!(
+            drain(dispatcher, received).await,
+            [("block", 1), ("block", 2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn rollback_retracts_announced_blocks_from_the_slot() {
+        let (dispatcher, received) = dispatcher(10, None);
+
+        for number in 1..=3 {
+            dispatcher.announce(&recent(number), None);
+        }
+
+        dispatcher.rollback(recent(2).cardano_slot).await;
+
+        // Retracted blocks can be announced again, e.g. on another fork.
+        assert!(dispatcher.should_announce(&recent(2)));
+        assert!(!dispatcher.should_announce(&recent(1)));
+
+        dispatcher.rollback(recent(2).cardano_slot).await;
+
+        assert_eq!(
+            drain(dispatcher, received).await,
+            [
+                ("block", 1),
+                ("block", 2),
+                ("block", 3),
+                ("retract", 2),
+                ("retract", 3)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_the_newest_announcements_are_remembered() {
+        let (dispatcher, received) = dispatcher(2 * ANNOUNCED_CAPACITY, None);
+        let newest = ANNOUNCED_CAPACITY as u64 + 1;
+
+        for number in 1..=newest {
+            dispatcher.announce(&recent(number), None);
+        }
+
+        assert!(dispatcher.should_announce(&recent(1)));
+        assert!(!dispatcher.should_announce(&recent(2)));
+
+        dispatcher.rollback(0).await;
+
+        let retracted: Vec<u64> = drain(dispatcher, received)
+            .await
+            .into_iter()
+            .filter(|(kind, _)| *kind == "retract")
+            .map(|(_, number)| number)
+            .collect();
+
+        assert_eq!(retracted, (2..=newest).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn full_queues_drop_announcements_but_not_retractions() {
+        let gate = Arc::new(Semaphore::new(0));
+        let (dispatcher, mut received) = dispatcher(1, Some(gate.clone()));
+        let dispatcher = Arc::new(dispatcher);
+
+        dispatcher.announce(&recent(1), None);
+
+        // The sink is busy with block 1, so block 2 fills its queue and block
+        // 3 has nowhere to go.
+        assert_eq!(summary(&received.recv().await.unwrap()), ("block", 1));
+
+        dispatcher.announce(&recent(2), None);
+        dispatcher.announce(&recent(3), None);
+
+        let rollback = tokio::spawn({
+            let dispatcher = dispatcher.clone();
+
+            async move { dispatcher.rollback(0).await }
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(!rollback.is_finished());
+
+        gate.add_permits(Semaphore::MAX_PERMITS);
+        rollback.await.unwrap();
+
+        let dispatcher = Arc::into_inner(dispatcher).unwrap();
+
+        // Block 3 was claimed before its queue turned out to be full, so it's
+        // retracted like the others.
+        assert_eq!(
+            drain(dispatcher, received).await,
+            [("block", 2), ("retract", 1), ("retract", 2), ("retract", 3)]
+        );
+    }
+
+    /// Replies with `script` in order, then succeeds, recording the block
+    /// number of every attempt and whether it got through.
+    #[derive(Default)]
+    struct Scripted {
+        script: Mutex<VecDeque<Result<(), NotifyError>>>,
+        attempts: Arc<Mutex<Vec<(u64, bool)>>>,
+    }
+
+    impl Scripted {
+        fn new(script: impl IntoIterator<Item = Result<(), NotifyError>>) -> Self {
+            Self {
+                script: Mutex::new(script.into_iter().collect()),
+                attempts: Arc::default(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Notifier for Scripted {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        async fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+            let result = self.script.lock().unwrap().pop_front().unwrap_or(Ok(()));
+
+            self.attempts
+                .lock()
+                .unwrap()
+                .push((notification.stored().block.number, result.is_ok()));
+
+            result
+        }
+    }
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+            max_attempts: Some(max_attempts),
+        }
+    }
+
+    fn failed() -> Result<(), NotifyError> {
+        Err(NotifyError::Failed(miette::miette!("down")))
+    }
+
+    fn rate_limited() -> Result<(), NotifyError> {
+        Err(NotifyError::RateLimited(Duration::from_millis(1)))
+    }
+
+    /// Deliver a retraction of each of `numbers` to `sink`, returning its
+    /// attempts once the queue is drained.
+    async fn deliver_all(sink: Scripted, numbers: &[u64], max_attempts: u32) -> Vec<(u64, bool)> {
+        let attempts = sink.attempts.clone();
+        let (sender, receiver) = mpsc::channel(numbers.len());
+
+        for &number in numbers {
+            let stored = StoredBlock::fixture(number, number * 20);
+
+            sender
+                .try_send(Notification::Retract(Box::new(stored)))
+                .unwrap();
+        }
+
+        drop(sender);
+
+        deliver(Box::new(sink), receiver, policy(max_attempts)).await;
+
+        let attempts = attempts.lock().unwrap().clone();
+
+        attempts
+    }
+
+    #[tokio::test]
+    async fn failures_are_retried_until_delivered() {
+        let sink = Scripted::new([failed(), failed()]);
+
+        assert_eq!(
+            deliver_all(sink, &[1, 2], 5).await,
+            [(1, false), (1, false), (1, true), (2, true)]
+        );
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts_and_moves_on() {
+        let sink = Scripted::new([failed(), failed(), failed()]);
+
+        assert_eq!(
+            deliver_all(sink, &[1, 2], 3).await,
+            [(1, false), (1, false), (1, false), (2, true)]
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limits_count_as_attempts() {
+        let sink = Scripted::new([rate_limited(), failed(), rate_limited(), rate_limited()]);
+
+        assert_eq!(
+            deliver_all(sink, &[1, 2], 3).await,
+            [(1, false), (1, false), (1, false), (2, false), (2, true)]
+        );
+    }
+
+    fn response(status: u16, retry_after: Option<&str>) -> reqwest::Response {
+        let mut builder = axum::http::Response::builder().status(status);
+
+        if let Some(retry_after) = retry_after {
+            builder = builder.header("retry-after", retry_after);
+        }
+
+        reqwest::Response::from(builder.body("").unwrap())
+    }
+
+    #[test]
+    fn too_many_requests_waits_as_long_as_asked() {
+        let cases = [
+            (Some("2"), Duration::from_secs(2)),
+            (Some("0.5"), Duration::from_millis(500)),
+            (Some("soon"), DEFAULT_RETRY_AFTER),
+            (None, DEFAULT_RETRY_AFTER),
+        ];
+
+        for (header, expected) in cases {
+            match check_response(response(429, header)) {
+                Err(NotifyError::RateLimited(retry_after)) => assert_eq!(retry_after, expected),
+                other => panic!("{header:?}: expected a rate limit, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn other_statuses_fail_or_pass_through() {
+        assert!(check_response(response(200, None)).is_ok());
+        assert!(matches!(
+            check_response(response(500, Some("2"))),
+            Err(NotifyError::Failed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn request_errors_leave_out_the_url() {
+        // Nothing listens on port 1, so this fails before any response.
+        let error = reqwest::Client::new()
+            .post("http://127.0.0.1:1/botSECRET/sendMessage")
+            .send()
+            .await
+            .unwrap_err();
+
+        assert!(format!("{error:?}").contains("SECRET"));
+
+        let NotifyError::Failed(report) = request_error(error) else {
+            panic!("expected a failure");
+        };
+
+        assert!(!format!("{report:?}").contains("SECRET"));
+        assert!(!report.to_string().contains("SECRET"));
+    }
+}
@@ -0,0 +1,209 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, StatusCode, Url};
+use serde::Deserialize;
+use serenity::all::{Colour, CreateEmbed};
+use tracing::{info, warn};
+
+use super::{
+    check_response, request_error, Announcement, Notification, Notifier, NotifyError,
+    ANNOUNCED_CAPACITY,
+};
+use crate::database::StoredBlock;
+
+mod embed;
+
+pub use embed::{EmbedField, EmbedFields, EmbedTemplate};
+
+/// Posts an embed to a Discord webhook, and deletes it again if the block is
+/// rolled back.
+///
+/// Requests go through reqwest rather than serenity's client, so a 429's
+/// `Retry-After` reaches the dispatcher like it does for the other sinks.
+pub struct DiscordNotifier {
+    client: reqwest::Client,
+    url: Url,
+    template: EmbedTemplate,
+    /// Posted message ids by block number, for the most recent
+    /// [`ANNOUNCED_CAPACITY`] blocks. Older ones can't be rolled back.
+    messages: Mutex<BTreeMap<u64, String>>,
+}
+
+/// The part of a posted message that's needed to delete it.
+#[derive(Deserialize)]
+struct Message {
+    id: String,
+}
+
+impl DiscordNotifier {
+    /// Check the webhook behind `url`, failing only if it's malformed or
+    /// Discord says it doesn't exist. If Discord can't be reached, the sink
+    /// starts anyway and the first announcement tries again.
+    pub async fn connect(url: String, template: EmbedTemplate) -> miette::Result<Self> {
+        let url = match Url::parse(&url) {
+            Ok(url) if !url.cannot_be_a_base() => url,
+            // Neither error holds the URL, whose path holds the webhook's token.
+            _ => miette::bail!("invalid DISCORD_WEBHOOK_URL"),
+        };
+
+        let notifier = Self {
+            client: reqwest::Client::new(),
+            url,
+            template,
+            messages: Mutex::new(BTreeMap::new()),
+        };
+
+        let request = notifier.client.get(notifier.url.clone());
+
+        if let Err(error) = startup_request(request).await? {
+            warn!(?error, "couldn't reach discord, will retry when announcing");
+        }
+
+        Ok(notifier)
+    }
+
+    /// Post a test embed, to check that announcements will get through.
+    /// Like [`connect`](Self::connect), only fails on a bad webhook.
+    pub async fn self_test(&self) -> miette::Result<()> {
+        let embed = CreateEmbed::new()
+            .description("### seine is connected\nNew blocks will be announced here.")
+            .colour(Colour::DARK_PURPLE);
+
+        match startup_request(self.execute(embed)).await? {
+            Ok(_) => info!("discord self-test posted"),
+            Err(error) => warn!(?error, "couldn't post the discord self-test"),
+        }
+
+        Ok(())
+    }
+
+    /// Post `embed`, waiting for the message so it can be deleted on rollback.
+    fn execute(&self, embed: CreateEmbed) -> RequestBuilder {
+        self.client
+            .post(self.url.clone())
+            .query(&[("wait", "true")])
+            .json(&serde_json::json!({ "embeds": [embed] }))
+    }
+
+    async fn announce(&self, announcement: &Announcement) -> Result<(), NotifyError> {
+        let response = self
+            .execute(self.template.render(announcement))
+            .send()
+            .await
+            .map_err(request_error)?;
+
+        let message: Message = check_response(response)?
+            .json()
+            .await
+            .map_err(request_error)?;
+
+        let mut messages = self.messages.lock().expect("messages lock poisoned");
+
+        messages.insert(announcement.stored.block.number, message.id);
+
+        while messages.len() > ANNOUNCED_CAPACITY {
+            messages.pop_first();
+        }
+
+        Ok(())
+    }
+
+    async fn retract(&self, stored: &StoredBlock) -> Result<(), NotifyError> {
+        let message_id = self
+            .messages
+            .lock()
+            .expect("messages lock poisoned")
+            .get(&stored.block.number)
+            .cloned();
+
+        // Never posted, so nothing to take back.
+        let Some(message_id) = message_id else {
+            return Ok(());
+        };
+
+        let mut url = self.url.clone();
+
+        url.path_segments_mut()
+            .expect("checked in connect")
+            .extend(["messages", &message_id]);
+
+        let response = self
+            .client
+            .delete(url)
+            .send()
+            .await
+            .map_err(request_error)?;
+
+        // Someone already deleted it by hand.
+        if response.status() != StatusCode::NOT_FOUND {
+            check_response(response)?;
+        }
+
+        self.messages
+            .lock()
+            .expect("messages lock poisoned")
+            .remove(&stored.block.number);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+        match notification {
+            Notification::Block(announcement) => self.announce(announcement).await,
+            Notification::Retract(stored) => self.retract(stored).await,
+        }
+    }
+}
+
+/// Send a request at startup. Fails if Discord rejected the webhook itself,
+/// which retrying won't fix, and otherwise returns whether it got through.
+async fn startup_request(request: RequestBuilder) -> miette::Result<Result<(), NotifyError>> {
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(error) => return Ok(Err(request_error(error))),
+    };
+
+    let status = response.status();
+
+    if matches!(status.as_u16(), 401 | 403 | 404) {
+        miette::bail!("invalid DISCORD_WEBHOOK_URL: discord responded {status}");
+    }
+
+    Ok(check_response(response).map(|_| ()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_malformed_urls_without_echoing_them() {
+        for url in ["not a url SECRET", "mailto:SECRET@example.com"] {
+            let Err(report) =
+                DiscordNotifier::connect(url.to_string(), EmbedTemplate::default()).await
+            else {
+                panic!("expected {url} to be rejected");
+            };
+
+            assert!(!format!("{report:?}").contains("SECRET"));
+        }
+    }
+
+    #[tokio::test]
+    async fn starts_when_discord_is_unreachable() {
+        // Nothing listens on port 1.
+        let url = "http://127.0.0.1:1/api/webhooks/1/SECRET".to_string();
+
+        assert!(DiscordNotifier::connect(url, EmbedTemplate::default())
+            .await
+            .is_ok());
+    }
+}
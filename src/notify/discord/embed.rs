@@ -0,0 +1,474 @@
+//! What a Discord announcement shows, configured from the environment.
+
+use std::str::FromStr;
+
+use num_traits::ToPrimitive;
+use serenity::all::{Colour, CreateEmbed};
+
+use crate::{
+    block::TunaBlock,
+    config::env_or,
+    consensus,
+    notify::{explorer_url, Announcement},
+};
+
+/// A detail that can be shown under an announcement's title.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedField {
+    /// The miner's payment key hash or NFT, shortened.
+    Miner,
+    Epoch,
+    LeadingZeros,
+    Target,
+    /// Time between this block and the one before it.
+    SincePrevious,
+    /// How much harder or easier mining got, when it changed.
+    Difficulty,
+    Reward,
+    Hash,
+    Nonce,
+}
+
+impl FromStr for EmbedField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "miner" => Ok(EmbedField::Miner),
+            "epoch" => Ok(EmbedField::Epoch),
+            "leading_zeros" => Ok(EmbedField::LeadingZeros),
+            "target" => Ok(EmbedField::Target),
+            "since_previous" => Ok(EmbedField::SincePrevious),
+            "difficulty" => Ok(EmbedField::Difficulty),
+            "reward" => Ok(EmbedField::Reward),
+            "hash" => Ok(EmbedField::Hash),
+            "nonce" => Ok(EmbedField::Nonce),
+            _ => Err(format!("unknown embed field {s}")),
+        }
+    }
+}
+
+/// Comma-separated fields, in the order they are shown.
+#[derive(Debug, Clone)]
+pub struct EmbedFields(Vec<EmbedField>);
+
+impl Default for EmbedFields {
+    fn default() -> Self {
+        EmbedFields(vec![
+            EmbedField::Miner,
+            EmbedField::Epoch,
+            EmbedField::LeadingZeros,
+            EmbedField::Target,
+            EmbedField::SincePrevious,
+            EmbedField::Difficulty,
+            EmbedField::Reward,
+            EmbedField::Hash,
+            EmbedField::Nonce,
+        ])
+    }
+}
+
+impl FromStr for EmbedFields {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(EmbedFields)
+    }
+}
+
+/// What kind of announcement a block gets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Block,
+    /// The first block of an epoch.
+    Epoch,
+    /// The first block of a halving era.
+    Halving,
+}
+
+impl Event {
+    fn of(number: u64) -> Self {
+        let Some(previous) = number.checked_sub(1) else {
+            return Event::Block;
+        };
+
+        if consensus::halving_era(number) != consensus::halving_era(previous) {
+            Event::Halving
+        } else if consensus::epoch(number) != consensus::epoch(previous) {
+            Event::Epoch
+        } else {
+            Event::Block
+        }
+    }
+}
+
+/// Titles for each kind of announcement, and the fields shown under them.
+///
+/// Titles can use `{number}`, `{epoch}`, `{era}` and `{reward}`.
+#[derive(Debug, Clone)]
+pub struct EmbedTemplate {
+    pub title: String,
+    pub epoch_title: String,
+    pub halving_title: String,
+    pub fields: EmbedFields,
+}
+
+impl Default for EmbedTemplate {
+    fn default() -> Self {
+        Self {
+            title: "New Block Mined: #{number}".to_string(),
+            epoch_title: "Epoch {epoch} Begins: #{number}".to_string(),
+            halving_title: "Halving! The reward is now {reward}: #{number}".to_string(),
+            fields: EmbedFields::default(),
+        }
+    }
+}
+
+impl EmbedTemplate {
+    /// Override the defaults with `DISCORD_TITLE`, `DISCORD_EPOCH_TITLE`,
+    /// `DISCORD_HALVING_TITLE` and `DISCORD_FIELDS`.
+    pub fn from_env() -> miette::Result<Self> {
+        let default = Self::default();
+
+        Ok(Self {
+            title: env_or("DISCORD_TITLE", default.title)?,
+            epoch_title: env_or("DISCORD_EPOCH_TITLE", default.epoch_title)?,
+            halving_title: env_or("DISCORD_HALVING_TITLE", default.halving_title)?,
+            fields: env_or("DISCORD_FIELDS", default.fields)?,
+        })
+    }
+
+    pub fn render(&self, announcement: &Announcement) -> CreateEmbed {
+        let stored = &announcement.stored;
+        let number = stored.block.number;
+
+        let (title, colour) = match Event::of(number) {
+            Event::Block => (&self.title, Colour::DARK_PURPLE),
+            Event::Epoch => (&self.epoch_title, Colour::GOLD),
+            Event::Halving => (&self.halving_title, Colour::RED),
+        };
+
+        let title = title
+            .replace("{number}", &number.to_string())
+            .replace("{epoch}", &stored.epoch.to_string())
+            .replace("{era}", &consensus::halving_era(number).to_string())
+            .replace("{reward}", &tuna(consensus::reward(number)));
+
+        let mut embed = CreateEmbed::new()
+            .description(format!(
+                "### [{title}]({})",
+                explorer_url(&stored.cardano_tx_hash)
+            ))
+            .colour(colour);
+
+        for field in &self.fields.0 {
+            if let Some((name, value, inline)) = field.render(announcement) {
+                embed = embed.field(name, value, inline);
+            }
+        }
+
+        embed
+    }
+}
+
+impl EmbedField {
+    /// Name, value and whether it is inline, or nothing when there is
+    /// nothing to show.
+    fn render(&self, announcement: &Announcement) -> Option<(&'static str, String, bool)> {
+        let block = &announcement.stored.block;
+        let previous = announcement.previous.as_ref().map(|stored| &stored.block);
+
+        match self {
+            EmbedField::Miner => {
+                let miner = block.payment_cred.as_ref().or(block.nft_cred.as_ref())?;
+
+                Some(("Miner", format!("`{}`", shorten(miner)), true))
+            }
+            EmbedField::Epoch => Some(("Epoch", announcement.stored.epoch.to_string(), true)),
+            EmbedField::LeadingZeros => {
+                Some(("Leading Zeros", block.leading_zeros.to_string(), true))
+            }
+            EmbedField::Target => Some(("Target", block.target_number.to_string(), true)),
+            EmbedField::SincePrevious => {
+                let elapsed = block
+                    .current_posix_time
+                    .checked_sub(previous?.current_posix_time)?;
+
+                Some(("Since Previous", format_duration(elapsed), true))
+            }
+            EmbedField::Difficulty => {
+                difficulty_change(previous?, block).map(|change| ("Difficulty", change, false))
+            }
+            EmbedField::Reward => Some(("Reward", tuna(consensus::reward(block.number)), true)),
+            EmbedField::Hash => Some(("Hash", format!("`{}`", block.current_hash), false)),
+            EmbedField::Nonce => Some(("Nonce", format!("`{}`", block.nonce.as_ref()?), false)),
+        }
+    }
+}
+
+/// How the difficulty changed since `previous`, if it did.
+fn difficulty_change(previous: &TunaBlock, block: &TunaBlock) -> Option<String> {
+    if previous.leading_zeros == block.leading_zeros
+        && previous.target_number == block.target_number
+    {
+        return None;
+    }
+
+    // Each leading zero is a hex digit, and a lower target is harder to beat.
+    let zeros = block.leading_zeros as i32 - previous.leading_zeros as i32;
+
+    let factor =
+        16f64.powi(zeros) * previous.target_number.to_f64()? / block.target_number.to_f64()?;
+
+    let change = if factor >= 1.0 {
+        format!("{factor:.2}× harder")
+    } else {
+        format!("{:.2}× easier", 1.0 / factor)
+    };
+
+    Some(format!(
+        "{change} ({} → {} zeros, target {} → {})",
+        previous.leading_zeros, block.leading_zeros, previous.target_number, block.target_number
+    ))
+}
+
+/// The start and end of a long hex string.
+fn shorten(hex: &str) -> String {
+    if hex.len() <= 16 {
+        return hex.to_string();
+    }
+
+    format!("{}…{}", &hex[..8], &hex[hex.len() - 8..])
+}
+
+/// Milliseconds as hours, minutes and seconds.
+fn format_duration(millis: u64) -> String {
+    let secs = millis / 1000;
+
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    if hours > 0 {
+        format!("{hours}h {minutes}m {secs}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {secs}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+/// An amount in the smallest unit, as TUNA.
+fn tuna(amount: u64) -> String {
+    let whole = amount / 100_000_000;
+    let fraction = amount % 100_000_000;
+
+    if fraction == 0 {
+        return format!("{whole} TUNA");
+    }
+
+    let fraction = format!("{fraction:08}");
+
+    format!("{whole}.{} TUNA", fraction.trim_end_matches('0'))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::database::StoredBlock;
+
+    /// Block `number`, mined 90 seconds after the block before it at the
+    /// same difficulty.
+    fn announcement(number: u64, with_previous: bool) -> Announcement {
+        let block = |number: u64| TunaBlock {
+            current_hash: "00ab".repeat(16),
+            leading_zeros: 8,
+            target_number: 65535u32.into(),
+            current_posix_time: number * 90_000,
+            nonce: Some("cafe".to_string()),
+            payment_cred: Some("1234567890abcdef1234567890abcdef".to_string()),
+            ..TunaBlock::fixture(number)
+        };
+
+        let stored = |number: u64| StoredBlock {
+            block: block(number),
+            ..StoredBlock::fixture(number, number * 20)
+        };
+
+        Announcement {
+            stored: stored(number),
+            previous: with_previous.then(|| stored(number - 1)),
+        }
+    }
+
+    fn rendered(template: &EmbedTemplate, announcement: &Announcement) -> Value {
+        serde_json::to_value(template.render(announcement)).unwrap()
+    }
+
+    fn field_names(embed: &Value) -> Vec<&str> {
+        embed["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["name"].as_str().unwrap())
+            .collect()
+    }
+
+    fn field<'a>(embed: &'a Value, name: &str) -> &'a str {
+        embed["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|field| field["name"] == name)
+            .and_then(|field| field["value"].as_str())
+            .unwrap_or_else(|| panic!("no {name} field"))
+    }
+
+    #[test]
+    fn default_template() {
+        let embed = rendered(&EmbedTemplate::default(), &announcement(42, true));
+
+        assert_eq!(
+            embed["description"],
+            format!(
+                "### [New Block Mined: #42](https://cexplorer.io/tx/{:064x})",
+                42
+            )
+        );
+        assert_eq!(embed["color"], Colour::DARK_PURPLE.0);
+
+        // The difficulty didn't change, so it isn't shown.
+        assert_eq!(
+            field_names(&embed),
+            [
+                "Miner",
+                "Epoch",
+                "Leading Zeros",
+                "Target",
+                "Since Previous",
+                "Reward",
+                "Hash",
+                "Nonce"
+            ]
+        );
+        assert_eq!(field(&embed, "Miner"), "`12345678…90abcdef`");
+        assert_eq!(field(&embed, "Epoch"), "1");
+        assert_eq!(field(&embed, "Since Previous"), "1m 30s");
+        assert_eq!(field(&embed, "Reward"), tuna(consensus::reward(42)));
+        assert_eq!(field(&embed, "Nonce"), "`cafe`");
+    }
+
+    #[test]
+    fn without_a_previous_block() {
+        let embed = rendered(&EmbedTemplate::default(), &announcement(42, false));
+
+        assert_eq!(
+            field_names(&embed),
+            [
+                "Miner",
+                "Epoch",
+                "Leading Zeros",
+                "Target",
+                "Reward",
+                "Hash",
+                "Nonce"
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_fields_and_titles() {
+        let template = EmbedTemplate {
+            title: "#{number} in epoch {epoch}, era {era}".to_string(),
+            fields: " nonce, ,miner ".parse().unwrap(),
+            ..EmbedTemplate::default()
+        };
+
+        let embed = rendered(&template, &announcement(42, true));
+
+        assert_eq!(
+            embed["description"],
+            format!(
+                "### [#42 in epoch 1, era 0](https://cexplorer.io/tx/{:064x})",
+                42
+            )
+        );
+        assert_eq!(field_names(&embed), ["Nonce", "Miner"]);
+
+        let empty = EmbedTemplate {
+            fields: "".parse().unwrap(),
+            ..EmbedTemplate::default()
+        };
+
+        assert!(rendered(&empty, &announcement(42, true))
+            .get("fields")
+            .is_none_or(|fields| fields.as_array().unwrap().is_empty()));
+
+        assert_eq!(
+            "miner,colour".parse::<EmbedFields>().unwrap_err(),
+            "unknown embed field colour"
+        );
+    }
+
+    #[test]
+    fn first_block_of_an_epoch() {
+        let embed = rendered(&EmbedTemplate::default(), &announcement(2016, true));
+
+        assert_eq!(Event::of(0), Event::Block);
+        assert_eq!(Event::of(2016), Event::Epoch);
+        assert!(embed["description"]
+            .as_str()
+            .unwrap()
+            .starts_with("### [Epoch 2 Begins: #2016]"));
+        assert_eq!(embed["color"], Colour::GOLD.0);
+    }
+
+    #[test]
+    fn difficulty_changes() {
+        let previous = TunaBlock {
+            leading_zeros: 8,
+            target_number: 32768u32.into(),
+            ..TunaBlock::fixture(1)
+        };
+
+        let harder = TunaBlock {
+            leading_zeros: 9,
+            ..previous.clone()
+        };
+
+        let easier = TunaBlock {
+            target_number: 65536u32.into(),
+            ..previous.clone()
+        };
+
+        assert_eq!(difficulty_change(&previous, &previous), None);
+        assert_eq!(
+            difficulty_change(&previous, &harder).unwrap(),
+            "16.00× harder (8 → 9 zeros, target 32768 → 32768)"
+        );
+        assert_eq!(
+            difficulty_change(&previous, &easier).unwrap(),
+            "2.00× easier (8 → 8 zeros, target 32768 → 65536)"
+        );
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(tuna(5_000_000_000), "50 TUNA");
+        assert_eq!(tuna(2_500_000_000), "25 TUNA");
+        assert_eq!(tuna(1_250_000_000), "12.5 TUNA");
+        assert_eq!(tuna(1), "0.00000001 TUNA");
+
+        assert_eq!(format_duration(999), "0s");
+        assert_eq!(format_duration(61_000), "1m 1s");
+        assert_eq!(format_duration(3_723_000), "1h 2m 3s");
+
+        assert_eq!(shorten("abcd"), "abcd");
+        assert_eq!(shorten(&"ab".repeat(16)), "abababab…abababab");
+    }
+}
@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+use miette::IntoDiagnostic;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+use super::{Notification, Notifier, NotifyError};
+
+/// Appends each notification as a line of JSON to a file, or to stdout for `-`.
+pub struct FileNotifier {
+    path: String,
+}
+
+impl FileNotifier {
+    pub fn new(path: String) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait]
+impl Notifier for FileNotifier {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let mut line = serde_json::to_vec(notification).into_diagnostic()?;
+
+        line.push(b'\n');
+
+        if self.path == "-" {
+            let mut stdout = tokio::io::stdout();
+
+            stdout.write_all(&line).await.into_diagnostic()?;
+
+            stdout.flush().await.into_diagnostic()?;
+
+            return Ok(());
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .into_diagnostic()?
+            .write_all(&line)
+            .await
+            .into_diagnostic()?;
+
+        Ok(())
+    }
+}
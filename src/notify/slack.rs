@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+
+use super::{check_response, explorer_url, request_error, Notification, Notifier, NotifyError};
+
+/// Posts a message to a Slack incoming webhook.
+pub struct SlackNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl SlackNotifier {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let text = match notification {
+            Notification::Block(announcement) => format!(
+                "*<{}|New Block Mined: #{}>*\nEpoch {}",
+                explorer_url(&announcement.stored.cardano_tx_hash),
+                announcement.stored.block.number,
+                announcement.stored.epoch
+            ),
+            Notification::Retract(stored) => {
+                format!("Block #{} was rolled back", stored.block.number)
+            }
+        };
+
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&serde_json::json!({ "text": text }))
+            .send()
+            .await
+            .map_err(request_error)?;
+
+        check_response(response)?;
+
+        Ok(())
+    }
+}
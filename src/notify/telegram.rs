@@ -0,0 +1,60 @@
+use async_trait::async_trait;
+
+use super::{check_response, explorer_url, request_error, Notification, Notifier, NotifyError};
+
+/// Sends a message to a Telegram chat through the Bot API.
+pub struct TelegramNotifier {
+    client: reqwest::Client,
+    token: String,
+    chat_id: String,
+}
+
+impl TelegramNotifier {
+    pub fn new(token: String, chat_id: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            token,
+            chat_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let text = match notification {
+            Notification::Block(announcement) => format!(
+                "<b><a href=\"{}\">New Block Mined: #{}</a></b>\nEpoch {}",
+                explorer_url(&announcement.stored.cardano_tx_hash),
+                announcement.stored.block.number,
+                announcement.stored.epoch
+            ),
+            Notification::Retract(stored) => {
+                format!("Block #{} was rolled back", stored.block.number)
+            }
+        };
+
+        let response = self
+            .client
+            .post(format!(
+                "https://api.telegram.org/bot{}/sendMessage",
+                self.token
+            ))
+            .json(&serde_json::json!({
+                "chat_id": self.chat_id,
+                "text": text,
+                "parse_mode": "HTML",
+            }))
+            .send()
+            .await
+            .map_err(request_error)?;
+
+        check_response(response)?;
+
+        Ok(())
+    }
+}
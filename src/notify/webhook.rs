@@ -0,0 +1,40 @@
+use async_trait::async_trait;
+
+use super::{check_response, request_error, Notification, Notifier, NotifyError};
+
+/// POSTs each notification to an arbitrary URL, as JSON tagged with its
+/// `type` and otherwise shaped like a block from the REST API.
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(notification)
+            .send()
+            .await
+            .map_err(request_error)?;
+
+        check_response(response)?;
+
+        Ok(())
+    }
+}
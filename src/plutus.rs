@@ -0,0 +1,363 @@
+//! Typed access to on-chain `PlutusData`.
+//!
+//! Every accessor checks the shape of the value it reads and fails with the
+//! path of the offending field (e.g. `state.target_number: expected int,
+//! found bytes`) instead of panicking on malformed or short data.
+
+use miette::bail;
+use num_bigint::{BigInt, BigUint, Sign};
+use utxorpc::spec::cardano::{self, big_int, plutus_data::PlutusData};
+
+/// A `PlutusData` value together with its path from the decoded root.
+#[derive(Debug, Clone)]
+pub struct Field<'a> {
+    data: &'a PlutusData,
+    path: String,
+}
+
+/// A constructor value with its fields.
+#[derive(Debug, Clone)]
+pub struct Constr<'a> {
+    constr: &'a cardano::Constr,
+    path: String,
+}
+
+impl<'a> Field<'a> {
+    /// Start decoding `data`, naming it `name` in errors.
+    pub fn root(data: &'a PlutusData, name: &str) -> Self {
+        Self {
+            data,
+            path: name.to_string(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn data(&self) -> &'a PlutusData {
+        self.data
+    }
+
+    pub fn constr(&self) -> miette::Result<Constr<'a>> {
+        match self.data {
+            PlutusData::Constr(constr) => Ok(Constr {
+                constr,
+                path: self.path.clone(),
+            }),
+            other => self.mismatch("constr", other),
+        }
+    }
+
+    /// An integer of any size, from any of the three on-chain encodings.
+    pub fn bigint(&self) -> miette::Result<BigInt> {
+        match self.data {
+            PlutusData::BigInt(cardano::BigInt { big_int: Some(n) }) => Ok(match n {
+                big_int::BigInt::Int(n) => BigInt::from(*n),
+                big_int::BigInt::BigUInt(bytes) => BigInt::from_bytes_be(Sign::Plus, bytes),
+                // Negative bignums encode `-1 - n`, as in CBOR tag 3.
+                big_int::BigInt::BigNInt(bytes) => -1 - BigInt::from_bytes_be(Sign::Plus, bytes),
+            }),
+            PlutusData::BigInt(_) => bail!("{}: missing integer", self.path),
+            other => self.mismatch("int", other),
+        }
+    }
+
+    /// A non-negative integer of any size.
+    pub fn biguint(&self) -> miette::Result<BigUint> {
+        let n = self.bigint()?;
+
+        match n.to_biguint() {
+            Some(n) => Ok(n),
+            None => bail!("{}: expected a non-negative int, found {n}", self.path),
+        }
+    }
+
+    /// An integer that fits in an `i64`.
+    pub fn int(&self) -> miette::Result<i64> {
+        let n = self.bigint()?;
+
+        match i64::try_from(&n) {
+            Ok(n) => Ok(n),
+            Err(_) => bail!("{}: {n} does not fit in 64 bits", self.path),
+        }
+    }
+
+    /// A non-negative integer that fits in a `u64`.
+    pub fn uint(&self) -> miette::Result<u64> {
+        let n = self.bigint()?;
+
+        match u64::try_from(&n) {
+            Ok(n) => Ok(n),
+            Err(_) => bail!(
+                "{}: expected a 64-bit non-negative int, found {n}",
+                self.path
+            ),
+        }
+    }
+
+    pub fn bytes(&self) -> miette::Result<&'a [u8]> {
+        match self.data {
+            PlutusData::BoundedBytes(bytes) => Ok(bytes),
+            other => self.mismatch("bytes", other),
+        }
+    }
+
+    /// Bytes, hex encoded.
+    pub fn hex(&self) -> miette::Result<String> {
+        self.bytes().map(hex::encode)
+    }
+
+    pub fn list(&self) -> miette::Result<Vec<Field<'a>>> {
+        let PlutusData::Array(array) = self.data else {
+            return self.mismatch("list", self.data);
+        };
+
+        array
+            .items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| self.child(item, format!("{}[{index}]", self.path)))
+            .collect()
+    }
+
+    /// Key-value pairs, in on-chain order.
+    pub fn map(&self) -> miette::Result<Vec<(Field<'a>, Field<'a>)>> {
+        let PlutusData::Map(map) = self.data else {
+            return self.mismatch("map", self.data);
+        };
+
+        map.pairs
+            .iter()
+            .enumerate()
+            .map(|(index, pair)| {
+                let path = format!("{}[{index}]", self.path);
+
+                let Some(key) = &pair.key else {
+                    bail!("{path}: missing key");
+                };
+
+                let Some(value) = &pair.value else {
+                    bail!("{path}: missing value");
+                };
+
+                Ok((
+                    self.child(key, format!("{path}.key"))?,
+                    self.child(value, format!("{path}.value"))?,
+                ))
+            })
+            .collect()
+    }
+
+    /// The value in the detailed JSON schema used by `cardano-cli`, e.g.
+    /// `{"constructor": 0, "fields": [{"int": 1}, {"bytes": "cafe"}]}`.
+    pub fn to_json(&self) -> serde_json::Value {
+        to_json(self.data)
+    }
+
+    fn child(&self, data: &'a cardano::PlutusData, path: String) -> miette::Result<Field<'a>> {
+        match &data.plutus_data {
+            Some(data) => Ok(Field { data, path }),
+            None => bail!("{path}: missing value"),
+        }
+    }
+
+    fn mismatch<T>(&self, expected: &str, found: &PlutusData) -> miette::Result<T> {
+        bail!("{}: expected {expected}, found {}", self.path, kind(found))
+    }
+}
+
+impl<'a> Constr<'a> {
+    /// The constructor index, decoded from the CBOR tag.
+    pub fn index(&self) -> Option<u64> {
+        constructor_index(self.constr)
+    }
+
+    /// The raw CBOR tag.
+    pub fn tag(&self) -> u32 {
+        self.constr.tag
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.constr.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constr.fields.is_empty()
+    }
+
+    /// Field `index`, named `name` in errors.
+    pub fn field(&self, index: usize, name: &str) -> miette::Result<Field<'a>> {
+        let path = format!("{}.{name}", self.path);
+
+        let Some(data) = self.constr.fields.get(index) else {
+            bail!(
+                "{path}: constructor has {} fields, expected at least {}",
+                self.constr.fields.len(),
+                index + 1
+            );
+        };
+
+        match &data.plutus_data {
+            Some(data) => Ok(Field { data, path }),
+            None => bail!("{path}: missing value"),
+        }
+    }
+}
+
+fn constructor_index(constr: &cardano::Constr) -> Option<u64> {
+    match constr.tag {
+        121..=127 => Some(u64::from(constr.tag) - 121),
+        1280..=1400 => Some(u64::from(constr.tag) - 1280 + 7),
+        102 => Some(constr.any_constructor),
+        _ => None,
+    }
+}
+
+fn to_json(data: &PlutusData) -> serde_json::Value {
+    use serde_json::{json, Value};
+
+    let wrapped = |data: &cardano::PlutusData| match &data.plutus_data {
+        Some(data) => to_json(data),
+        None => Value::Null,
+    };
+
+    match data {
+        PlutusData::Constr(constr) => json!({
+            "constructor": constructor_index(constr).unwrap_or(u64::from(constr.tag)),
+            "fields": constr.fields.iter().map(wrapped).collect::<Vec<_>>(),
+        }),
+        PlutusData::Map(map) => json!({
+            "map": map
+                .pairs
+                .iter()
+                .map(|pair| json!({
+                    "k": pair.key.as_ref().map_or(Value::Null, wrapped),
+                    "v": pair.value.as_ref().map_or(Value::Null, wrapped),
+                }))
+                .collect::<Vec<_>>(),
+        }),
+        // JSON numbers beyond 64 bits lose precision in most parsers, so those
+        // are written as decimal strings.
+        PlutusData::BigInt(_) => match Field::root(data, "int").bigint() {
+            Ok(n) => match i64::try_from(&n) {
+                Ok(n) => json!({ "int": n }),
+                Err(_) => json!({ "int": n.to_string() }),
+            },
+            Err(_) => json!({ "int": Value::Null }),
+        },
+        PlutusData::BoundedBytes(bytes) => json!({ "bytes": hex::encode(bytes) }),
+        PlutusData::Array(array) => json!({
+            "list": array.items.iter().map(wrapped).collect::<Vec<_>>(),
+        }),
+    }
+}
+
+fn kind(data: &PlutusData) -> &'static str {
+    match data {
+        PlutusData::Constr(_) => "constr",
+        PlutusData::Map(_) => "map",
+        PlutusData::BigInt(_) => "int",
+        PlutusData::BoundedBytes(_) => "bytes",
+        PlutusData::Array(_) => "list",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(n: big_int::BigInt) -> PlutusData {
+        PlutusData::BigInt(cardano::BigInt { big_int: Some(n) })
+    }
+
+    /// 2^64, one past `u64::MAX`, as big-endian bytes.
+    const TWO_TO_THE_64: [u8; 9] = [1, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    #[test]
+    fn small_ints_decode_directly() {
+        let data = int(big_int::BigInt::Int(-5));
+        let field = Field::root(&data, "n");
+
+        assert_eq!(field.bigint().unwrap(), BigInt::from(-5));
+        assert_eq!(field.int().unwrap(), -5);
+        assert!(field.uint().is_err());
+        assert!(field.biguint().is_err());
+    }
+
+    #[test]
+    fn big_uints_decode_beyond_64_bits() {
+        let data = int(big_int::BigInt::BigUInt(TWO_TO_THE_64.to_vec().into()));
+        let field = Field::root(&data, "n");
+
+        let expected = BigUint::from(u64::MAX) + 1u32;
+
+        assert_eq!(field.biguint().unwrap(), expected);
+        assert!(field.uint().is_err());
+        assert_eq!(
+            field.to_json(),
+            serde_json::json!({ "int": "18446744073709551616" })
+        );
+    }
+
+    #[test]
+    fn big_nints_decode_as_minus_one_minus_n() {
+        let data = int(big_int::BigInt::BigNInt(vec![0].into()));
+
+        assert_eq!(Field::root(&data, "n").int().unwrap(), -1);
+
+        let data = int(big_int::BigInt::BigNInt(TWO_TO_THE_64.to_vec().into()));
+        let field = Field::root(&data, "n");
+
+        let expected = -1 - BigInt::from(u64::MAX) - 1;
+
+        assert_eq!(field.bigint().unwrap(), expected);
+        assert!(field.biguint().is_err());
+    }
+
+    #[test]
+    fn errors_name_the_field() {
+        let data = PlutusData::BoundedBytes(vec![1].into());
+
+        let error = Field::root(&data, "state").uint().unwrap_err();
+
+        assert_eq!(error.to_string(), "state: expected int, found bytes");
+    }
+
+    fn constr(tag: u32, any_constructor: u64) -> cardano::Constr {
+        cardano::Constr {
+            tag,
+            any_constructor,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compact_tags_cover_the_first_seven_constructors() {
+        assert_eq!(constructor_index(&constr(121, 0)), Some(0));
+        assert_eq!(constructor_index(&constr(127, 0)), Some(6));
+    }
+
+    #[test]
+    fn extended_tags_continue_from_seven() {
+        assert_eq!(constructor_index(&constr(1280, 0)), Some(7));
+        assert_eq!(constructor_index(&constr(1400, 0)), Some(127));
+    }
+
+    #[test]
+    fn general_tag_carries_its_own_index() {
+        assert_eq!(constructor_index(&constr(102, 0)), Some(0));
+        assert_eq!(constructor_index(&constr(102, 1000)), Some(1000));
+    }
+
+    #[test]
+    fn other_tags_are_not_constructors() {
+        for tag in [0, 120, 128, 1279, 1401] {
+            assert_eq!(constructor_index(&constr(tag, 0)), None, "tag {tag}");
+        }
+    }
+}
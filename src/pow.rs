@@ -0,0 +1,445 @@
+//! Independent check of the proof of work behind each Fortuna block.
+//!
+//! The miner hashes a "target state" built from the block being spent and
+//! their nonce. The hash becomes the next block's `current_hash` and has to
+//! beat the difficulty of the block being spent.
+
+use std::{fmt, str::FromStr};
+
+use miette::{bail, IntoDiagnostic};
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+
+use crate::block::TunaBlock;
+
+/// What to do with a block whose proof of work doesn't check out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowMode {
+    /// Don't verify.
+    Off,
+    /// Log and count the failure, but index the block anyway.
+    Warn,
+    /// Refuse the block, stopping the indexer.
+    Strict,
+}
+
+impl FromStr for PowMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(PowMode::Off),
+            "warn" => Ok(PowMode::Warn),
+            "strict" => Ok(PowMode::Strict),
+            _ => Err(format!("expected off, warn or strict, found {s}")),
+        }
+    }
+}
+
+impl fmt::Display for PowMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowMode::Off => f.write_str("off"),
+            PowMode::Warn => f.write_str("warn"),
+            PowMode::Strict => f.write_str("strict"),
+        }
+    }
+}
+
+/// The miner credential hashed into a target state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Miner {
+    /// V1 target states have no miner field.
+    None,
+    /// A V2 payment key hash, or NFT policy and name.
+    Known(Vec<u8>),
+    /// A V2 miner credential seine can't decode, so the target state can't be
+    /// rebuilt.
+    Unknown,
+}
+
+/// What checking a block's proof of work found, short of a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    Verified,
+    /// The target state couldn't be rebuilt, so the proof of work can't be
+    /// checked either way.
+    Unverifiable,
+}
+
+/// The data a miner hashes, as `Constr 0` in this field order.
+///
+/// V2 adds the miner's credential (key hash, or NFT policy and name) after
+/// the nonce, so a solution can't be stolen by another miner.
+#[derive(Debug, Clone)]
+pub struct TargetState {
+    pub nonce: Vec<u8>,
+    pub miner: Option<Vec<u8>>,
+    pub block_number: u64,
+    pub current_hash: Vec<u8>,
+    pub leading_zeros: u64,
+    pub target_number: BigUint,
+    pub epoch_time: BigUint,
+}
+
+impl TargetState {
+    /// The target state for mining on top of `prev`.
+    pub fn new(prev: &TunaBlock, nonce: Vec<u8>, miner: Option<Vec<u8>>) -> miette::Result<Self> {
+        Ok(TargetState {
+            nonce,
+            miner,
+            block_number: prev.number,
+            current_hash: hex::decode(&prev.current_hash).into_diagnostic()?,
+            leading_zeros: prev.leading_zeros,
+            target_number: prev.target_number.clone(),
+            epoch_time: prev.epoch_time.clone(),
+        })
+    }
+
+    /// The Plutus data CBOR encoding, as `cbor.serialise` produces it on-chain.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        // Constructor 0 is tag 121, and non-empty field lists are indefinite.
+        out.extend_from_slice(&[0xd8, 0x79, 0x9f]);
+
+        encode_bytes(&mut out, &self.nonce);
+
+        if let Some(miner) = &self.miner {
+            encode_bytes(&mut out, miner);
+        }
+
+        encode_uint(&mut out, &self.block_number.into());
+        encode_bytes(&mut out, &self.current_hash);
+        encode_uint(&mut out, &self.leading_zeros.into());
+        encode_uint(&mut out, &self.target_number);
+        encode_uint(&mut out, &self.epoch_time);
+
+        out.push(0xff);
+
+        out
+    }
+
+    /// The double SHA-256 of the encoded state.
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256::digest(Sha256::digest(self.to_cbor())).into()
+    }
+}
+
+/// The difficulty a hash achieves: its number of leading zero hex digits and
+/// the 16-bit number formed by the four digits after them.
+pub fn difficulty(hash: &[u8]) -> (u64, u64) {
+    let nibbles: Vec<u8> = hash
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect();
+
+    let leading_zeros = nibbles.iter().take_while(|nibble| **nibble == 0).count();
+
+    let difficulty_number = nibbles
+        .iter()
+        .skip(leading_zeros)
+        .chain(std::iter::repeat(&0))
+        .take(4)
+        .fold(0, |number, nibble| number * 16 + u64::from(*nibble));
+
+    (leading_zeros as u64, difficulty_number)
+}
+
+/// Check that `next` was mined on top of `prev` with a valid proof of work.
+pub fn verify(prev: &TunaBlock, next: &TunaBlock, miner: &Miner) -> miette::Result<Verification> {
+    let miner = match miner {
+        Miner::None => None,
+        Miner::Known(miner) => Some(miner.clone()),
+        Miner::Unknown => return Ok(Verification::Unverifiable),
+    };
+
+    let Some(nonce) = &next.nonce else {
+        bail!("block {} has no nonce", next.number);
+    };
+
+    if next.number != prev.number + 1 {
+        bail!(
+            "block {} does not follow block {}",
+            next.number,
+            prev.number
+        );
+    }
+
+    let state = TargetState::new(prev, hex::decode(nonce).into_diagnostic()?, miner)?;
+
+    let hash = state.hash();
+
+    if hex::encode(hash) != next.current_hash {
+        bail!(
+            "block {} has hash {} but its target state hashes to {}",
+            next.number,
+            next.current_hash,
+            hex::encode(hash)
+        );
+    }
+
+    let (leading_zeros, difficulty_number) = difficulty(&hash);
+
+    let beats_target = leading_zeros > prev.leading_zeros
+        || (leading_zeros == prev.leading_zeros
+            && BigUint::from(difficulty_number) < prev.target_number);
+
+    if !beats_target {
+        bail!(
+            "block {} hash has {leading_zeros} leading zeros and difficulty {difficulty_number}, \
+             which doesn't beat {} leading zeros and target {}",
+            next.number,
+            prev.leading_zeros,
+            prev.target_number
+        );
+    }
+
+    Ok(Verification::Verified)
+}
+
+fn encode_header(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+
+    match n {
+        0..=23 => out.push(major | n as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, n as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+/// Plutus splits byte strings longer than 64 bytes into 64-byte chunks.
+fn encode_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    if bytes.len() <= 64 {
+        encode_header(out, 2, bytes.len() as u64);
+        out.extend_from_slice(bytes);
+    } else {
+        out.push(0x5f);
+
+        for chunk in bytes.chunks(64) {
+            encode_header(out, 2, chunk.len() as u64);
+            out.extend_from_slice(chunk);
+        }
+
+        out.push(0xff);
+    }
+}
+
+/// Integers beyond 64 bits are encoded as bignums (tag 2).
+fn encode_uint(out: &mut Vec<u8>, n: &BigUint) {
+    match u64::try_from(n) {
+        Ok(n) => encode_header(out, 0, n),
+        Err(_) => {
+            out.push(0xc2);
+            encode_bytes(out, &n.to_bytes_be());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The fixtures below were produced by a separate implementation of the
+    // target state encoding and mined against a low difficulty, so each
+    // `next` really carries a hash beating its `prev`.
+
+    fn block(number: u64, current_hash: &str, leading_zeros: u64, target: u64) -> TunaBlock {
+        TunaBlock {
+            current_hash: current_hash.to_string(),
+            leading_zeros,
+            target_number: target.into(),
+            ..TunaBlock::fixture(number)
+        }
+    }
+
+    fn mined(prev: &TunaBlock, hash: &str, nonce: &str) -> TunaBlock {
+        TunaBlock {
+            nonce: Some(nonce.to_string()),
+            ..block(prev.number + 1, hash, prev.leading_zeros, 0)
+        }
+    }
+
+    fn v1_pair() -> (TunaBlock, TunaBlock) {
+        let prev = TunaBlock {
+            epoch_time: 1_000_000u32.into(),
+            ..block(
+                17,
+                "1e78902d582e3c9f5c73142df5d1aaff01222cadf63ed51a9f2c32c3dbc12ad3",
+                1,
+                0x4000,
+            )
+        };
+
+        let next = mined(
+            &prev,
+            "01c2d8ce5910dd18ea8798bf93963083e386d23ac3934a79e4fce79024df1305",
+            "013b6301da288e463967c3a4b1def74d",
+        );
+
+        (prev, next)
+    }
+
+    fn v2_pair() -> (TunaBlock, TunaBlock, Miner) {
+        let prev = TunaBlock {
+            epoch_time: 5_000_000u32.into(),
+            ..block(
+                30300,
+                "933159ec230fbd7dae269311396d1ea30b7875ff1f5b57baf90e4db6c4f0ec14",
+                1,
+                0x4000,
+            )
+        };
+
+        let next = mined(
+            &prev,
+            "02a0f0d4d925d8c9ad5c19bbf1f9391a23602c87aa32ed6feef9895ec2753b63",
+            "7179a26b0a784c4b323e86dde6d1ee92",
+        );
+
+        (prev, next, Miner::Known(vec![0x11; 28]))
+    }
+
+    fn flip_bit(hex_string: &str) -> String {
+        let mut bytes = hex::decode(hex_string).unwrap();
+
+        bytes[0] ^= 1;
+
+        hex::encode(bytes)
+    }
+
+    #[test]
+    fn v1_state_encodes_as_plutus_data() {
+        let state = TargetState {
+            nonce: vec![0xca, 0xfe],
+            miner: None,
+            block_number: 1,
+            current_hash: vec![0x00, 0xff],
+            leading_zeros: 4,
+            target_number: 65535u32.into(),
+            // Past 64 bits, so encoded as a bignum.
+            epoch_time: BigUint::from(u64::MAX) + 1u32,
+        };
+
+        assert_eq!(
+            hex::encode(state.to_cbor()),
+            "d8799f42cafe014200ff0419ffffc249010000000000000000ff"
+        );
+        assert_eq!(
+            hex::encode(state.hash()),
+            "7a62cb8dfbf8c21354e5d566258ad14eed72e3731bd629e14654edd2215ef18b"
+        );
+    }
+
+    #[test]
+    fn v2_state_puts_the_miner_after_the_nonce() {
+        let state = TargetState {
+            nonce: vec![0xca, 0xfe],
+            miner: Some(vec![0xab; 28]),
+            block_number: 1,
+            current_hash: vec![0x00, 0xff],
+            leading_zeros: 4,
+            target_number: 65535u32.into(),
+            epoch_time: 1000u32.into(),
+        };
+
+        assert_eq!(
+            hex::encode(state.to_cbor()),
+            "d8799f42cafe581cabababababababababababababababababababababababababababab\
+             014200ff0419ffff1903e8ff"
+        );
+        assert_eq!(
+            hex::encode(state.hash()),
+            "51a83b21be688d2f8cb9f8d432a7497a926570f6f69366bcf6cc1bdf6d73eade"
+        );
+    }
+
+    #[test]
+    fn long_byte_strings_are_chunked() {
+        let mut out = Vec::new();
+
+        encode_bytes(&mut out, &[7; 65]);
+
+        assert_eq!(&out[..3], &[0x5f, 0x58, 64]);
+        assert_eq!(&out[67..70], &[0x41, 7, 0xff]);
+        assert_eq!(out.len(), 70);
+    }
+
+    #[test]
+    fn difficulty_counts_zero_nibbles_and_the_next_four() {
+        assert_eq!(difficulty(&[0x00, 0x01, 0x23, 0x45, 0x67]), (3, 0x1234));
+        assert_eq!(difficulty(&[0x00, 0x00]), (4, 0));
+    }
+
+    #[test]
+    fn verifies_a_v1_block() {
+        let (prev, next) = v1_pair();
+
+        assert_eq!(
+            verify(&prev, &next, &Miner::None).unwrap(),
+            Verification::Verified
+        );
+    }
+
+    #[test]
+    fn verifies_a_v2_block() {
+        let (prev, next, miner) = v2_pair();
+
+        assert_eq!(
+            verify(&prev, &next, &miner).unwrap(),
+            Verification::Verified
+        );
+    }
+
+    #[test]
+    fn rejects_a_bit_flipped_nonce() {
+        let (prev, mut next) = v1_pair();
+
+        next.nonce = next.nonce.as_deref().map(flip_bit);
+
+        assert!(verify(&prev, &next, &Miner::None).is_err());
+
+        let (prev, mut next, miner) = v2_pair();
+
+        next.nonce = next.nonce.as_deref().map(flip_bit);
+
+        assert!(verify(&prev, &next, &miner).is_err());
+    }
+
+    #[test]
+    fn rejects_another_miner() {
+        let (prev, next, _) = v2_pair();
+
+        assert!(verify(&prev, &next, &Miner::Known(vec![0x22; 28])).is_err());
+    }
+
+    #[test]
+    fn rejects_a_hash_that_misses_the_target() {
+        let (mut prev, next) = v1_pair();
+
+        prev.target_number = 0x1000u32.into();
+
+        assert!(verify(&prev, &next, &Miner::None).is_err());
+    }
+
+    #[test]
+    fn unknown_miners_are_unverifiable_rather_than_invalid() {
+        let (prev, next, _) = v2_pair();
+
+        assert_eq!(
+            verify(&prev, &next, &Miner::Unknown).unwrap(),
+            Verification::Unverifiable
+        );
+    }
+}
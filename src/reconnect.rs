@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use tonic::Code;
+
+use crate::{config::env_or, retry::RetryPolicy};
+
+/// How the indexer reconnects to Dolos after the tip stream ends: reads
+/// `RECONNECT_INITIAL_DELAY_MS` (default 1000), `RECONNECT_MAX_DELAY_MS`
+/// (default 60000) and `RECONNECT_MAX_ATTEMPTS` (default 0, meaning unlimited).
+pub fn policy_from_env() -> miette::Result<RetryPolicy> {
+    let max_attempts = env_or("RECONNECT_MAX_ATTEMPTS", 0)?;
+
+    Ok(RetryPolicy {
+        initial_delay: Duration::from_millis(env_or("RECONNECT_INITIAL_DELAY_MS", 1_000)?),
+        max_delay: Duration::from_millis(env_or("RECONNECT_MAX_DELAY_MS", 60_000)?),
+        max_attempts: (max_attempts > 0).then_some(max_attempts),
+    })
+}
+
+/// Why a sync session with Dolos ended.
+#[derive(Debug)]
+pub enum SyncError {
+    /// Worth reconnecting, e.g. a dropped connection or an unavailable node.
+    Transient(miette::Report),
+    /// Reconnecting won't help, e.g. no intersection could be found.
+    Fatal(miette::Report),
+}
+
+impl From<miette::Report> for SyncError {
+    fn from(report: miette::Report) -> Self {
+        SyncError::Fatal(report)
+    }
+}
+
+/// Classify an error from `follow_tip`. Anything other than a connection
+/// problem means the node rejected our intersection points.
+pub fn intersect_error(error: utxorpc::Error) -> SyncError {
+    if is_connection_error(&error) {
+        SyncError::Transient(miette::miette!("failed to follow tip: {error}"))
+    } else {
+        SyncError::Fatal(miette::miette!("failed to intersect: {error}"))
+    }
+}
+
+/// Classify an error that ended an established tip stream.
+pub fn stream_error(error: utxorpc::Error) -> SyncError {
+    if is_connection_error(&error) {
+        SyncError::Transient(miette::miette!("tip stream ended: {error}"))
+    } else {
+        SyncError::Fatal(miette::miette!("tip stream failed: {error}"))
+    }
+}
+
+/// Whether `error` came from the connection rather than the request, so the
+/// same request may succeed on a new connection.
+///
+/// A connection reset mid-call surfaces as `Internal` or `Unknown`, whether it
+/// happens while intersecting or while streaming.
+fn is_connection_error(error: &utxorpc::Error) -> bool {
+    match error {
+        utxorpc::Error::TransportError(_) => true,
+        utxorpc::Error::GrpcError(status) => matches!(
+            status.code(),
+            Code::Unavailable
+                | Code::DeadlineExceeded
+                | Code::Cancelled
+                | Code::Aborted
+                | Code::ResourceExhausted
+                | Code::Internal
+                | Code::Unknown
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_transient(error: SyncError) -> bool {
+        matches!(error, SyncError::Transient(_))
+    }
+
+    #[test]
+    fn grpc_codes_are_classified_the_same_while_intersecting_and_streaming() {
+        let cases = [
+            (Code::Ok, false),
+            (Code::Cancelled, true),
+            (Code::Unknown, true),
+            (Code::InvalidArgument, false),
+            (Code::DeadlineExceeded, true),
+            (Code::NotFound, false),
+            (Code::AlreadyExists, false),
+            (Code::PermissionDenied, false),
+            (Code::ResourceExhausted, true),
+            (Code::FailedPrecondition, false),
+            (Code::Aborted, true),
+            (Code::OutOfRange, false),
+            (Code::Unimplemented, false),
+            (Code::Internal, true),
+            (Code::Unavailable, true),
+            (Code::DataLoss, false),
+            (Code::Unauthenticated, false),
+        ];
+
+        for (code, transient) in cases {
+            let error = || utxorpc::Error::GrpcError(tonic::Status::new(code, "test"));
+
+            assert_eq!(
+                is_transient(intersect_error(error())),
+                transient,
+                "{code:?}"
+            );
+            assert_eq!(is_transient(stream_error(error())), transient, "{code:?}");
+        }
+    }
+
+    #[test]
+    fn other_errors_are_fatal() {
+        let error = || utxorpc::Error::ParseError("bad block".to_string());
+
+        assert!(!is_transient(intersect_error(error())));
+        assert!(!is_transient(stream_error(error())));
+        assert!(!is_transient(miette::miette!("no intersection").into()));
+    }
+}
@@ -0,0 +1,233 @@
+use tracing::warn;
+use utxorpc::spec::cardano::plutus_data::PlutusData;
+
+use crate::plutus::Field;
+
+/// The redeemer spending the Fortuna V2 state when a block is mined.
+#[derive(Debug, Clone)]
+pub struct V2Redeemer {
+    /// Hex encoded.
+    pub nonce: String,
+    /// `None` if the credential uses a constructor this version of seine
+    /// doesn't know about.
+    pub miner: Option<MinerCredential>,
+}
+
+/// Who mined a Fortuna V2 block, with whatever extra data they attached.
+#[derive(Debug, Clone)]
+pub enum MinerCredential {
+    /// A verification key hash.
+    Pkh {
+        key_hash: String,
+        data: serde_json::Value,
+    },
+    /// Holder of an NFT, identified by its policy and asset name.
+    Nft {
+        policy: String,
+        name: String,
+        data: serde_json::Value,
+    },
+}
+
+impl V2Redeemer {
+    pub fn decode(data: &PlutusData) -> miette::Result<Self> {
+        let redeemer = Field::root(data, "redeemer").constr()?;
+
+        Ok(V2Redeemer {
+            nonce: redeemer.field(0, "nonce")?.hex()?,
+            miner: MinerCredential::decode(&redeemer.field(1, "miner")?)?,
+        })
+    }
+}
+
+impl MinerCredential {
+    pub fn decode(field: &Field) -> miette::Result<Option<Self>> {
+        let miner = field.constr()?;
+
+        match miner.index() {
+            Some(0) => Ok(Some(MinerCredential::Pkh {
+                key_hash: miner.field(0, "key_hash")?.hex()?,
+                data: miner.field(1, "data")?.to_json(),
+            })),
+            // Field 2 only locates the NFT within the transaction.
+            Some(1) => Ok(Some(MinerCredential::Nft {
+                policy: miner.field(0, "policy")?.hex()?,
+                name: miner.field(1, "name")?.hex()?,
+                data: miner.field(3, "data")?.to_json(),
+            })),
+            _ => {
+                warn!(
+                    path = miner.path(),
+                    tag = miner.tag(),
+                    "unknown miner credential constructor"
+                );
+
+                Ok(None)
+            }
+        }
+    }
+
+    /// The miner's payment key hash, if they mined with one.
+    pub fn payment_cred(&self) -> Option<String> {
+        match self {
+            MinerCredential::Pkh { key_hash, .. } => Some(key_hash.clone()),
+            MinerCredential::Nft { .. } => None,
+        }
+    }
+
+    /// The NFT's policy and asset name concatenated, if they mined with one.
+    pub fn nft_cred(&self) -> Option<String> {
+        match self {
+            MinerCredential::Pkh { .. } => None,
+            MinerCredential::Nft { policy, name, .. } => Some(format!("{policy}{name}")),
+        }
+    }
+
+    /// The extra data the miner attached.
+    pub fn data(&self) -> &serde_json::Value {
+        match self {
+            MinerCredential::Pkh { data, .. } | MinerCredential::Nft { data, .. } => data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utxorpc::spec::cardano;
+
+    use super::*;
+
+    fn wrap(data: PlutusData) -> cardano::PlutusData {
+        cardano::PlutusData {
+            plutus_data: Some(data),
+        }
+    }
+
+    fn constr(index: u32, fields: Vec<PlutusData>) -> PlutusData {
+        PlutusData::Constr(cardano::Constr {
+            tag: 121 + index,
+            fields: fields.into_iter().map(wrap).collect(),
+            ..Default::default()
+        })
+    }
+
+    fn bytes(bytes: &[u8]) -> PlutusData {
+        PlutusData::BoundedBytes(bytes.to_vec().into())
+    }
+
+    fn int(n: i64) -> PlutusData {
+        PlutusData::BigInt(cardano::BigInt {
+            big_int: Some(cardano::big_int::BigInt::Int(n)),
+        })
+    }
+
+    fn redeemer(miner: PlutusData) -> PlutusData {
+        constr(0, vec![bytes(&[0xca, 0xfe]), miner])
+    }
+
+    fn error(data: PlutusData) -> String {
+        V2Redeemer::decode(&data).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn decodes_a_key_hash_miner() {
+        let data = redeemer(constr(0, vec![bytes(&[0xab; 28]), constr(0, vec![])]));
+
+        let redeemer = V2Redeemer::decode(&data).unwrap();
+        let miner = redeemer.miner.unwrap();
+
+        assert_eq!(redeemer.nonce, "cafe");
+        assert!(
+            matches!(&miner, MinerCredential::Pkh { key_hash, .. } if *key_hash == "ab".repeat(28))
+        );
+        assert_eq!(miner.payment_cred(), Some("ab".repeat(28)));
+        assert_eq!(miner.nft_cred(), None);
+        assert_eq!(
+            miner.data(),
+            &serde_json::json!({ "constructor": 0, "fields": [] })
+        );
+    }
+
+    #[test]
+    fn decodes_an_nft_miner() {
+        let output_ref = constr(0, vec![bytes(&[0x01; 32]), int(0)]);
+
+        let data = redeemer(constr(
+            1,
+            vec![bytes(&[0x11; 28]), bytes(b"lord"), output_ref, int(5)],
+        ));
+
+        let miner = V2Redeemer::decode(&data).unwrap().miner.unwrap();
+
+        assert!(matches!(
+            &miner,
+            MinerCredential::Nft { policy, name, .. }
+                if *policy == "11".repeat(28) && name == "6c6f7264"
+        ));
+        assert_eq!(miner.payment_cred(), None);
+        assert_eq!(
+            miner.nft_cred(),
+            Some(format!("{}6c6f7264", "11".repeat(28)))
+        );
+        assert_eq!(miner.data(), &serde_json::json!({ "int": 5 }));
+    }
+
+    #[test]
+    fn unknown_constructors_leave_the_miner_unknown() {
+        for tag in [123, 1280] {
+            let miner = PlutusData::Constr(cardano::Constr {
+                tag,
+                fields: vec![wrap(bytes(&[0xab; 28]))],
+                ..Default::default()
+            });
+
+            let redeemer = V2Redeemer::decode(&redeemer(miner)).unwrap();
+
+            assert_eq!(redeemer.nonce, "cafe");
+            assert!(redeemer.miner.is_none(), "tag {tag}");
+        }
+    }
+
+    #[test]
+    fn malformed_redeemers_name_the_field() {
+        assert_eq!(error(bytes(&[0])), "redeemer: expected constr, found bytes");
+        assert_eq!(
+            error(constr(0, vec![bytes(&[0])])),
+            "redeemer.miner: constructor has 1 fields, expected at least 2"
+        );
+        assert_eq!(
+            error(constr(0, vec![int(1), constr(0, vec![])])),
+            "redeemer.nonce: expected bytes, found int"
+        );
+        assert_eq!(
+            error(redeemer(bytes(&[0xab; 28]))),
+            "redeemer.miner: expected constr, found bytes"
+        );
+    }
+
+    #[test]
+    fn malformed_credentials_name_the_field() {
+        assert_eq!(
+            error(redeemer(constr(0, vec![bytes(&[0xab; 28])]))),
+            "redeemer.miner.data: constructor has 1 fields, expected at least 2"
+        );
+        assert_eq!(
+            error(redeemer(constr(0, vec![int(7), constr(0, vec![])]))),
+            "redeemer.miner.key_hash: expected bytes, found int"
+        );
+        assert_eq!(
+            error(redeemer(constr(
+                1,
+                vec![bytes(&[0x11; 28]), int(0), constr(0, vec![]), int(5)]
+            ))),
+            "redeemer.miner.name: expected bytes, found int"
+        );
+        assert_eq!(
+            error(redeemer(constr(
+                1,
+                vec![bytes(&[0x11; 28]), bytes(b"lord"), constr(0, vec![])]
+            ))),
+            "redeemer.miner.data: constructor has 3 fields, expected at least 4"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Exponential backoff with jitter, for reconnecting to Dolos and for
+/// redelivering notifications.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Give up after this many consecutive failed attempts. `None` retries forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl RetryPolicy {
+    /// Whether another attempt is allowed after `attempt` consecutive failures.
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        self.max_attempts.is_none_or(|max| attempt < max)
+    }
+
+    /// The delay before retry number `attempt` (starting at 1): exponential
+    /// backoff capped at `max_delay`, with the upper half randomised so many
+    /// callers don't retry in lockstep.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+
+        let backoff = self
+            .initial_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        let half = backoff / 2;
+
+        half + half.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_attempts: Option<u32>) -> RetryPolicy {
+        RetryPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1_000),
+            max_attempts,
+        }
+    }
+
+    #[test]
+    fn delay_doubles_within_a_jitter_of_half() {
+        let policy = policy(None);
+
+        for (attempt, backoff) in [(1, 100), (2, 200), (3, 400), (4, 800)] {
+            let backoff = Duration::from_millis(backoff);
+
+            for _ in 0..20 {
+                let delay = policy.delay(attempt);
+
+                assert!(
+                    delay >= backoff / 2 && delay <= backoff,
+                    "{attempt}: {delay:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn delay_is_capped() {
+        let policy = policy(None);
+
+        for attempt in [5, 6, 32, 33, u32::MAX] {
+            let delay = policy.delay(attempt);
+
+            assert!(delay >= policy.max_delay / 2 && delay <= policy.max_delay);
+        }
+    }
+
+    #[test]
+    fn retries_up_to_max_attempts() {
+        assert!((0..1_000).all(|attempt| policy(None).should_retry(attempt)));
+
+        let limited = policy(Some(3));
+
+        assert!(limited.should_retry(0));
+        assert!(limited.should_retry(2));
+        assert!(!limited.should_retry(3));
+        assert!(!limited.should_retry(4));
+    }
+}
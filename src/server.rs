@@ -0,0 +1,114 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+    Json, Router,
+};
+use miette::IntoDiagnostic;
+use tokio::net::TcpListener;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt,
+};
+
+use crate::{api, database::BlockStore, feed::Feed, health::Health, metrics};
+
+/// Everything the HTTP handlers need.
+#[derive(Clone)]
+pub struct AppState {
+    pub store: Arc<dyn BlockStore>,
+    pub health: Arc<Health>,
+    pub feed: Feed,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Readiness {
+    ready: bool,
+    connected: bool,
+    store_reachable: bool,
+    synced: bool,
+    lag_seconds: Option<i64>,
+}
+
+/// Serve the HTTP endpoints on `listener` until the process exits.
+pub async fn serve(listener: TcpListener, state: AppState) -> miette::Result<()> {
+    let app = Router::new()
+        .route("/metrics", get(render_metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/events", get(events))
+        .merge(api::router())
+        .with_state(state);
+
+    axum::serve(listener, app).await.into_diagnostic()
+}
+
+async fn render_metrics() -> Result<String, (StatusCode, String)> {
+    metrics::render().map_err(|error| (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()))
+}
+
+/// Liveness: the sync loop made progress within `LIVENESS_TIMEOUT_SECS`, so a
+/// hung indexer gets restarted.
+async fn healthz(State(state): State<AppState>) -> (StatusCode, &'static str) {
+    if state.health.is_live() {
+        (StatusCode::OK, "ok")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "stalled")
+    }
+}
+
+/// Readiness: following the chain, able to reach the store, and close to the tip.
+///
+/// Answered from what the indexer last saw rather than by querying the store,
+/// which for D1 would cost API calls on every probe.
+async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let connected = state.health.is_connected();
+    let store_reachable = state.health.is_store_reachable();
+    let synced = state.health.is_synced();
+
+    let ready = connected && store_reachable && synced;
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(Readiness {
+            ready,
+            connected,
+            store_reachable,
+            synced,
+            lag_seconds: state.health.lag_seconds(),
+        }),
+    )
+}
+
+/// Live feed of applied blocks and rollbacks as server-sent events.
+///
+/// A client that falls behind receives a `lagged` event with the number of
+/// events it missed, and should refetch from the REST API.
+async fn events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(state.feed.subscribe()).map(|event| {
+        let event = match event {
+            Ok(event) => Event::default()
+                .event(event.name())
+                .json_data(&event)
+                .unwrap_or_else(|_| Event::default().event("error")),
+            Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                Event::default().event("lagged").data(missed.to_string())
+            }
+        };
+
+        Ok(event)
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}